@@ -0,0 +1,35 @@
+use egui::TextureId;
+use egui_sdl2_gl::painter::Painter;
+
+mod audio_panel;
+mod audio_visualizer;
+mod debugger_window;
+mod gbs_player;
+mod hex_editor;
+mod oam_viewer;
+mod tile_viewer;
+mod tilemap_viewer;
+
+pub use audio_panel::AudioPanel;
+pub use audio_visualizer::AudioVisualizer;
+pub use debugger_window::DebuggerWindow;
+pub use gbs_player::GbsPlayerWindow;
+pub use hex_editor::HexEditor;
+pub use oam_viewer::OamViewer;
+pub use tile_viewer::TileViewer;
+pub use tilemap_viewer::TilemapViewer;
+
+// Creates the texture on first use, then updates it with the new pixels
+fn update_texture(painter: &mut Painter, texture: &mut Option<TextureId>, size: (usize, usize), rgba: Vec<u8>) -> TextureId {
+    match texture {
+        Some(id) => {
+            painter.update_user_texture_rgba8_data(*id, rgba);
+            *id
+        }
+        None => {
+            let id = painter.new_user_texture_rgba8(size, rgba, false);
+            *texture = Some(id);
+            id
+        }
+    }
+}
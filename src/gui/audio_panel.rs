@@ -0,0 +1,66 @@
+use egui::Context;
+use crate::rusty_boy::{Mixer, RustyBoy};
+
+const CHANNEL_NAMES: [&str; 4] = ["CH1 Square", "CH2 Square", "CH3 Wave", "CH4 Noise"];
+// meter falloff per gui frame, so short peaks stay readable
+const METER_DECAY: f32 = 0.92;
+
+// Audio window with per channel mute, solo, gain and live level meters
+pub struct AudioPanel {
+    pub open: bool,
+    meters: [f32; 4],
+}
+
+impl AudioPanel {
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            meters: [0.0; 4],
+        }
+    }
+
+    pub fn show(&mut self, ctx: &Context, rusty: &mut RustyBoy) {
+        if !self.open {
+            return;
+        }
+        // levels are taken every frame so they only cover the last frame
+        for (meter, level) in self.meters.iter_mut().zip(rusty.audio_levels()) {
+            *meter = level.max(*meter * METER_DECAY);
+        }
+
+        let mut open = self.open;
+        let mut mixer = rusty.mixer();
+        egui::Window::new("Audio").open(&mut open).resizable(false).show(ctx, |ui| {
+            egui::Grid::new("mixer_grid").striped(true).show(ui, |ui| {
+                ui.strong("Channel");
+                ui.strong("Mute");
+                ui.strong("Solo");
+                ui.strong("Gain");
+                ui.strong("Level");
+                ui.end_row();
+
+                for (n, name) in CHANNEL_NAMES.iter().enumerate() {
+                    ui.label(*name);
+                    ui.checkbox(&mut mixer.mute[n], "");
+                    ui.checkbox(&mut mixer.solo[n], "");
+                    ui.add(egui::Slider::new(&mut mixer.gain[n], 0.0..=2.0).fixed_decimals(2));
+                    ui.add(egui::ProgressBar::new(self.meters[n]).desired_width(120.0));
+                    ui.end_row();
+                }
+            });
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.label("Master volume");
+                ui.add(egui::Slider::new(&mut mixer.master, 0.0..=1.0).fixed_decimals(2));
+            });
+            if ui.button("Reset").clicked() {
+                mixer = Mixer::new();
+            }
+        });
+        self.open = open;
+
+        if mixer != rusty.mixer() {
+            rusty.set_mixer(mixer);
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use std::collections::VecDeque;
+use egui::{pos2, vec2, Color32, Context, Rect, Sense, Stroke, Ui};
+use crate::rusty_boy::RustyBoy;
+
+const CHANNEL_NAMES: [&str; 4] = ["CH1 Square", "CH2 Square", "CH3 Wave", "CH4 Noise"];
+const CHANNEL_COLORS: [Color32; 4] = [
+    Color32::from_rgb(90, 200, 90),
+    Color32::from_rgb(90, 160, 230),
+    Color32::from_rgb(230, 170, 60),
+    Color32::from_rgb(210, 100, 200),
+];
+const NOTE_NAMES: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+// points shown per scope, one point is 16 cycles so this is about 7.8 ms
+const SCOPE_LEN: usize = 2048;
+const SCOPE_SIZE: [f32; 2] = [400.0, 56.0];
+
+// Audio debug window with per channel oscilloscopes, the decoded channel registers and the wave ram
+pub struct AudioVisualizer {
+    pub open: bool,
+    // stops taking new scope points
+    freeze: bool,
+    // last dac outputs, twice the shown length so the trigger has room to search
+    history: VecDeque<[f32; 4]>,
+}
+
+impl AudioVisualizer {
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            freeze: false,
+            history: VecDeque::with_capacity(SCOPE_LEN * 2),
+        }
+    }
+
+    pub fn show(&mut self, ctx: &Context, rusty: &mut RustyBoy) {
+        // the apu only keeps history while the window is open
+        rusty.set_audio_scope(self.open);
+        if !self.open {
+            return;
+        }
+        let points = rusty.take_audio_scope();
+        if !self.freeze {
+            self.history.extend(points);
+            let excess = self.history.len().saturating_sub(SCOPE_LEN * 2);
+            self.history.drain(..excess);
+        }
+
+        let mut open = self.open;
+        egui::Window::new("Audio Visualizer").open(&mut open).resizable(false).show(ctx, |ui| {
+            let Some((status, wave_ram)) = rusty.audio_status() else {
+                ui.label("Load a ROM to see its audio");
+                return;
+            };
+            ui.checkbox(&mut self.freeze, "Freeze");
+            for (n, name) in CHANNEL_NAMES.iter().enumerate() {
+                ui.label(*name);
+                self.draw_scope(ui, n);
+            }
+            ui.separator();
+
+            egui::Grid::new("channel_status").striped(true).show(ui, |ui| {
+                for heading in ["Channel", "On", "DAC", "Frequency", "Note", "Shape", "Volume", "Length"] {
+                    ui.strong(heading);
+                }
+                ui.end_row();
+
+                for (n, channel) in status.iter().enumerate() {
+                    ui.colored_label(CHANNEL_COLORS[n], CHANNEL_NAMES[n]);
+                    ui.label(if channel.enabled { "on" } else { "off" });
+                    ui.label(if channel.dac { "on" } else { "off" });
+                    ui.monospace(format!("{:.1} Hz", channel.frequency));
+                    // the noise frequency is a clock rate, not a pitch
+                    ui.monospace(if n < 3 { note_name(channel.frequency) } else { String::new() });
+                    ui.label(channel.shape);
+                    ui.monospace(format!("{:2}", channel.volume));
+                    ui.monospace(match channel.length {
+                        Some(length) => length.to_string(),
+                        None => String::from("-"),
+                    });
+                    ui.end_row();
+                }
+            });
+            ui.separator();
+
+            ui.label("CH3 wave RAM");
+            draw_wave_ram(ui, &wave_ram);
+            let hex: Vec<String> = wave_ram.iter().map(|byte| format!("{:02X}", byte)).collect();
+            ui.monospace(hex.join(" "));
+        });
+        self.open = open;
+    }
+
+    // Draws the last SCOPE_LEN points of a channel, starting at a rising edge so periodic waves stand still
+    fn draw_scope(&self, ui: &mut Ui, channel: usize) {
+        let (response, painter) = ui.allocate_painter(vec2(SCOPE_SIZE[0], SCOPE_SIZE[1]), Sense::hover());
+        let rect = response.rect;
+        painter.rect_filled(rect, 2.0, Color32::from_gray(20));
+        painter.hline(rect.x_range(), rect.center().y, Stroke::new(1.0, Color32::from_gray(50)));
+
+        let levels: Vec<f32> = self.history.iter().map(|point| point[channel]).collect();
+        if levels.len() < 2 {
+            return;
+        }
+        let start = trigger(&levels);
+        let shown = &levels[start..levels.len().min(start + SCOPE_LEN)];
+        let points: Vec<_> = shown
+            .iter()
+            .enumerate()
+            .map(|(i, level)| {
+                let x = rect.left() + rect.width() * i as f32 / (SCOPE_LEN - 1) as f32;
+                // dac outputs are -1 to 1, with a small margin
+                let y = rect.center().y - level * rect.height() * 0.45;
+                pos2(x, y)
+            })
+            .collect();
+        painter.add(egui::Shape::line(points, Stroke::new(1.0, CHANNEL_COLORS[channel])));
+    }
+}
+
+// First rising crossing of the middle level in the older half of the history, or the start of the shown window
+fn trigger(levels: &[f32]) -> usize {
+    let fallback = levels.len().saturating_sub(SCOPE_LEN);
+    let (low, high) = levels.iter().fold((f32::MAX, f32::MIN), |(low, high), level| (low.min(*level), high.max(*level)));
+    if high - low < 0.01 {
+        return fallback;
+    }
+    let middle = (low + high) / 2.0;
+    (1..=fallback).find(|&i| levels[i - 1] < middle && levels[i] >= middle).unwrap_or(fallback)
+}
+
+// One bar per 4 bit sample, the upper nibble of each byte plays first
+fn draw_wave_ram(ui: &mut Ui, wave_ram: &[u8; 16]) {
+    let (response, painter) = ui.allocate_painter(vec2(SCOPE_SIZE[0], 64.0), Sense::hover());
+    let rect = response.rect;
+    painter.rect_filled(rect, 2.0, Color32::from_gray(20));
+    let width = rect.width() / 32.0;
+    for (i, byte) in wave_ram.iter().enumerate() {
+        for (j, sample) in [byte >> 4, byte & 0xf].into_iter().enumerate() {
+            let x = rect.left() + (i * 2 + j) as f32 * width;
+            let height = rect.height() * (sample as f32 + 1.0) / 16.0;
+            let bar = Rect::from_min_max(pos2(x + 1.0, rect.bottom() - height), pos2(x + width - 1.0, rect.bottom()));
+            painter.rect_filled(bar, 0.0, CHANNEL_COLORS[2]);
+        }
+    }
+}
+
+// Nearest note to a frequency with its octave, e.g. A4 for 440 hz
+fn note_name(frequency: f32) -> String {
+    if frequency < 8.0 {
+        return String::from("-");
+    }
+    let semitone = (12.0 * (frequency / 440.0).log2()).round() as i32 + 69;
+    format!("{}{}", NOTE_NAMES[semitone.rem_euclid(12) as usize], semitone / 12 - 1)
+}
@@ -0,0 +1,332 @@
+use egui::{Color32, Context, RichText, Sense};
+use crate::rusty_boy::debugger::{Breakpoint, Compare, Condition, Register, WatchKind, Watchpoint};
+use crate::rusty_boy::RustyBoy;
+
+// instructions shown ahead of and from pc
+const LINES_BEFORE: usize = 8;
+const LINES_AFTER: usize = 16;
+// stack words shown from sp
+const STACK_DEPTH: u16 = 8;
+
+// Debugger actions collected while the cpu is borrowed for drawing
+enum Action {
+    Pause,
+    Resume,
+    StepInto,
+    StepOver,
+    StepOut,
+    RunTo(u16),
+    Toggle(u16, Option<u16>),
+    Remove(usize),
+    Enable(usize, bool),
+    Add(Breakpoint),
+    SetWatchpoints(Vec<Watchpoint>),
+}
+
+// Debug window with execution controls, disassembly, registers, interrupts, stack and breakpoints
+pub struct DebuggerWindow {
+    pub open: bool,
+    // new breakpoint form
+    address: String,
+    conditional: bool,
+    register: Register,
+    compare: Compare,
+    value: String,
+    // new watchpoint form
+    watch_start: String,
+    watch_end: String,
+    watch_kind: WatchKind,
+    error: String,
+}
+
+impl DebuggerWindow {
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            address: String::new(),
+            conditional: false,
+            register: Register::A,
+            compare: Compare::Equal,
+            value: String::new(),
+            watch_start: String::new(),
+            watch_end: String::new(),
+            watch_kind: WatchKind::Write,
+            error: String::new(),
+        }
+    }
+
+    pub fn show(&mut self, ctx: &Context, rusty: &mut RustyBoy) {
+        let mut open = self.open;
+        let mut actions = Vec::new();
+        egui::Window::new("Debugger").open(&mut open).default_width(520.0).show(ctx, |ui| {
+            let Some(cpu) = rusty.cpu() else {
+                ui.label("No ROM loaded");
+                return;
+            };
+            let debugger = rusty.debugger();
+            let paused = debugger.paused();
+
+            // execution controls
+            ui.horizontal(|ui| {
+                if paused {
+                    if ui.button("Continue").clicked() {
+                        actions.push(Action::Resume);
+                    }
+                } else if ui.button("Pause").clicked() {
+                    actions.push(Action::Pause);
+                }
+                if ui.add_enabled(paused, egui::Button::new("Step into")).clicked() {
+                    actions.push(Action::StepInto);
+                }
+                if ui.add_enabled(paused, egui::Button::new("Step over")).clicked() {
+                    actions.push(Action::StepOver);
+                }
+                if ui.add_enabled(paused, egui::Button::new("Step out")).clicked() {
+                    actions.push(Action::StepOut);
+                }
+            });
+            ui.label(if paused { debugger.reason() } else { "Running" });
+            ui.separator();
+
+            let registers = cpu.registers.clone();
+            ui.columns(2, |col| {
+                // disassembly, click the gutter to toggle a breakpoint, right click a line to run to it
+                col[0].vertical(|ui| {
+                    ui.strong("Disassembly");
+                    for instruction in rusty.disassembly(LINES_BEFORE, LINES_AFTER) {
+                        let address = instruction.address;
+                        let bank = instruction.bank;
+                        if let Some(label) = &instruction.label {
+                            ui.monospace(RichText::new(format!("{}:", label)).color(Color32::LIGHT_BLUE));
+                        }
+                        ui.horizontal(|ui| {
+                            let marker = if debugger.has_breakpoint(address, bank) { "●" } else { "○" };
+                            let gutter = ui.add(egui::Label::new(RichText::new(marker).color(Color32::RED)).sense(Sense::click()));
+                            if gutter.clicked() {
+                                actions.push(Action::Toggle(address, bank));
+                            }
+
+                            let bytes: Vec<String> = instruction.bytes.iter().map(|b| format!("{:02X}", b)).collect();
+                            let mut text = RichText::new(format!("{:04X}  {:<8}  {}", address, bytes.join(" "), instruction.text)).monospace();
+                            if address == registers.pc {
+                                text = text.background_color(Color32::from_rgb(80, 80, 20)).color(Color32::WHITE);
+                            }
+                            ui.add(egui::Label::new(text).sense(Sense::click())).context_menu(|ui| {
+                                if ui.add_enabled(paused, egui::Button::new("Run to cursor")).clicked() {
+                                    actions.push(Action::RunTo(address));
+                                    ui.close_menu();
+                                }
+                                if ui.button("Toggle breakpoint").clicked() {
+                                    actions.push(Action::Toggle(address, bank));
+                                    ui.close_menu();
+                                }
+                            });
+                        });
+                    }
+                });
+
+                col[1].vertical(|ui| {
+                    ui.strong("Registers");
+                    egui::Grid::new("debugger_registers").show(ui, |ui| {
+                        for register in [Register::AF, Register::BC, Register::DE, Register::HL, Register::SP, Register::PC] {
+                            ui.monospace(register.name());
+                            ui.monospace(format!("{:04X}", register.read(&registers)));
+                            ui.end_row();
+                        }
+                    });
+
+                    let flags: Vec<String> = ["z", "n", "h", "c"]
+                        .iter()
+                        .map(|flag| {
+                            let set = registers.get_flag(flag).unwrap_or(false);
+                            if set { flag.to_uppercase() } else { String::from("-") }
+                        })
+                        .collect();
+                    ui.monospace(format!("Flags {}", flags.join(" ")));
+
+                    let mb = &cpu.motherboard;
+                    ui.monospace(format!("IME   {}", if mb.i_master.get() { "on" } else { "off" }));
+                    ui.monospace(format!("IE    {:02X} %{:05b}", mb.i_enable.get(), mb.i_enable.get() & 0b11111));
+                    ui.monospace(format!("IF    {:02X} %{:05b}", mb.i_flag.get(), mb.i_flag.get() & 0b11111));
+
+                    ui.separator();
+                    ui.strong("Stack");
+                    for n in 0..STACK_DEPTH {
+                        let address = registers.sp.wrapping_add(n * 2);
+                        let value = u16::from_le_bytes([cpu.memory.peek(address), cpu.memory.peek(address.wrapping_add(1))]);
+                        ui.monospace(format!("{:04X}  {:04X}", address, value));
+                    }
+
+                    ui.separator();
+                    ui.strong("Call stack");
+                    if cpu.call_stack.is_empty() {
+                        ui.label("-");
+                    }
+                    let name = |address: u16| cpu.describe_address(address).unwrap_or_else(|| format!("${:04X}", address));
+                    for frame in cpu.call_stack.iter().rev() {
+                        ui.monospace(format!("{}  <- {}", name(frame.target), name(frame.return_address)));
+                    }
+                });
+            });
+
+            ui.separator();
+            ui.strong("Breakpoints");
+            for (index, breakpoint) in debugger.breakpoints.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    let mut enabled = breakpoint.enabled;
+                    if ui.checkbox(&mut enabled, "").changed() {
+                        actions.push(Action::Enable(index, enabled));
+                    }
+                    let condition = breakpoint.condition.map(|c| format!("  if {}", c)).unwrap_or_default();
+                    let bank = breakpoint.bank.map(|bank| format!(" bank {}", bank)).unwrap_or_default();
+                    let label = match breakpoint.bank {
+                        Some(bank) => cpu.symbols.label(bank, breakpoint.address),
+                        None => cpu.symbol(breakpoint.address),
+                    };
+                    let label = label.map(|name| format!(" {}", name)).unwrap_or_default();
+                    ui.monospace(format!("${:04X}{}{}{}", breakpoint.address, bank, label, condition));
+                    if ui.small_button("Remove").clicked() {
+                        actions.push(Action::Remove(index));
+                    }
+                });
+            }
+
+            // new breakpoint form
+            ui.horizontal(|ui| {
+                ui.label("$");
+                ui.add(egui::TextEdit::singleline(&mut self.address).desired_width(80.0).hint_text("addr or label"));
+                ui.checkbox(&mut self.conditional, "if");
+                ui.add_enabled_ui(self.conditional, |ui| {
+                    egui::ComboBox::from_id_salt("breakpoint_register")
+                        .width(40.0)
+                        .selected_text(self.register.name())
+                        .show_ui(ui, |ui| {
+                            for register in Register::ALL {
+                                ui.selectable_value(&mut self.register, register, register.name());
+                            }
+                        });
+                    egui::ComboBox::from_id_salt("breakpoint_compare")
+                        .width(30.0)
+                        .selected_text(self.compare.symbol())
+                        .show_ui(ui, |ui| {
+                            for compare in Compare::ALL {
+                                ui.selectable_value(&mut self.compare, compare, compare.symbol());
+                            }
+                        });
+                    ui.label("$");
+                    ui.add(egui::TextEdit::singleline(&mut self.value).desired_width(40.0).hint_text("value"));
+                });
+                if ui.button("Add").clicked() {
+                    match self.parse_breakpoint(rusty) {
+                        Ok(breakpoint) => {
+                            actions.push(Action::Add(breakpoint));
+                            self.error.clear();
+                        }
+                        Err(error) => self.error = error,
+                    }
+                }
+            });
+
+            ui.separator();
+            ui.strong("Watchpoints");
+            let mut watchpoints = rusty.watchpoints().to_vec();
+            let mut changed = false;
+            let mut remove = None;
+            for (index, watchpoint) in watchpoints.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    changed |= ui.checkbox(&mut watchpoint.enabled, "").changed();
+                    let range = if watchpoint.start == watchpoint.end {
+                        format!("${:04X}", watchpoint.start)
+                    } else {
+                        format!("${:04X}-${:04X}", watchpoint.start, watchpoint.end)
+                    };
+                    ui.monospace(format!("{} {}", range, watchpoint.kind.name().to_lowercase()));
+                    if ui.small_button("Remove").clicked() {
+                        remove = Some(index);
+                    }
+                });
+            }
+
+            // new watchpoint form, the end address is optional
+            ui.horizontal(|ui| {
+                ui.label("$");
+                ui.add(egui::TextEdit::singleline(&mut self.watch_start).desired_width(40.0).hint_text("start"));
+                ui.label("- $");
+                ui.add(egui::TextEdit::singleline(&mut self.watch_end).desired_width(40.0).hint_text("end"));
+                for kind in WatchKind::ALL {
+                    ui.radio_value(&mut self.watch_kind, kind, kind.name());
+                }
+                if ui.button("Add").clicked() {
+                    match self.parse_watchpoint() {
+                        Ok(watchpoint) => {
+                            watchpoints.push(watchpoint);
+                            changed = true;
+                            self.error.clear();
+                        }
+                        Err(error) => self.error = error,
+                    }
+                }
+            });
+            if let Some(index) = remove {
+                watchpoints.remove(index);
+                changed = true;
+            }
+            if changed {
+                actions.push(Action::SetWatchpoints(watchpoints));
+            }
+
+            if !self.error.is_empty() {
+                ui.colored_label(Color32::LIGHT_RED, &self.error);
+            }
+        });
+        self.open = open;
+
+        for action in actions {
+            match action {
+                Action::Pause => rusty.debugger_mut().pause(String::from("Paused")),
+                Action::Resume => rusty.debugger_mut().resume(),
+                Action::StepInto => rusty.debugger_mut().step_into(),
+                Action::StepOver => rusty.step_over(),
+                Action::StepOut => rusty.step_out(),
+                Action::RunTo(address) => rusty.debugger_mut().run_to(address),
+                Action::Toggle(address, bank) => rusty.debugger_mut().toggle_breakpoint(address, bank),
+                Action::Remove(index) => {
+                    rusty.debugger_mut().breakpoints.remove(index);
+                }
+                Action::Enable(index, enabled) => rusty.debugger_mut().breakpoints[index].enabled = enabled,
+                Action::Add(breakpoint) => rusty.debugger_mut().breakpoints.push(breakpoint),
+                Action::SetWatchpoints(watchpoints) => rusty.set_watchpoints(watchpoints),
+            }
+        }
+    }
+
+    // the address is hex or a symbol, symbols in switchable rom only hit in their own bank
+    fn parse_breakpoint(&self, rusty: &RustyBoy) -> Result<Breakpoint, String> {
+        let (address, bank) = match rusty.lookup_symbol(self.address.trim()) {
+            Some((bank, address)) if (0x4000..0x8000).contains(&address) => (address, Some(bank)),
+            Some((_, address)) => (address, None),
+            None => (parse_hex(&self.address)?, None),
+        };
+        let condition = if self.conditional {
+            let value = parse_hex(&self.value)?;
+            Some(Condition { register: self.register, compare: self.compare, value })
+        } else {
+            None
+        };
+        Ok(Breakpoint { address, bank, condition, enabled: true })
+    }
+
+    fn parse_watchpoint(&self) -> Result<Watchpoint, String> {
+        let start = parse_hex(&self.watch_start)?;
+        let end = if self.watch_end.trim().is_empty() { start } else { parse_hex(&self.watch_end)? };
+        if end < start {
+            return Err(String::from("Watchpoint range ends before it starts"));
+        }
+        Ok(Watchpoint { start, end, kind: self.watch_kind, enabled: true })
+    }
+}
+
+// Parses a hex number with an optional $ prefix
+fn parse_hex(text: &str) -> Result<u16, String> {
+    u16::from_str_radix(text.trim().trim_start_matches('$'), 16).map_err(|_| format!("Invalid hex value \"{}\"", text))
+}
@@ -0,0 +1,91 @@
+use egui::Context;
+use crate::rusty_boy::RustyBoy;
+
+// Music player window for gbs files, track selection, play time and fade out
+pub struct GbsPlayerWindow {
+    pub open: bool,
+}
+
+impl GbsPlayerWindow {
+    pub fn new() -> Self {
+        Self { open: false }
+    }
+
+    pub fn show(&mut self, ctx: &Context, rusty: &mut RustyBoy) {
+        if !self.open {
+            return;
+        }
+        let mut open = self.open;
+        let mut play = None;
+        egui::Window::new("GBS Player").open(&mut open).resizable(false).show(ctx, |ui| {
+            let Some(player) = rusty.gbs_mut() else {
+                ui.label("Load a .gbs file to play its music");
+                return;
+            };
+            let header = &player.header;
+            egui::Grid::new("gbs_info").show(ui, |ui| {
+                ui.label("Title");
+                ui.strong(&header.title);
+                ui.end_row();
+                ui.label("Author");
+                ui.label(&header.author);
+                ui.end_row();
+                ui.label("Copyright");
+                ui.label(&header.copyright);
+                ui.end_row();
+                ui.label("Driver");
+                ui.label(if header.uses_timer() { "Timer interrupt" } else { "VBlank interrupt" });
+                ui.end_row();
+            });
+            ui.separator();
+
+            let track = player.track();
+            let count = player.track_count();
+            ui.horizontal(|ui| {
+                if ui.add_enabled(track > 0, egui::Button::new("Prev")).clicked() {
+                    play = Some(track - 1);
+                }
+                egui::ComboBox::from_id_salt("gbs_track")
+                    .selected_text(format!("Track {} / {}", track + 1, count))
+                    .show_ui(ui, |ui| {
+                        for n in 0..count {
+                            if ui.selectable_label(n == track, format!("Track {}", n + 1)).clicked() {
+                                play = Some(n);
+                            }
+                        }
+                    });
+                if ui.add_enabled(track + 1 < count, egui::Button::new("Next")).clicked() {
+                    play = Some(track + 1);
+                }
+                if ui.button("Restart").clicked() {
+                    play = Some(track);
+                }
+            });
+
+            let elapsed = player.elapsed() as u32;
+            let status = if player.finished() {
+                "finished"
+            } else if player.gain() < 1.0 {
+                "fading out"
+            } else {
+                "playing"
+            };
+            ui.monospace(format!("{:02}:{:02}  {}", elapsed / 60, elapsed % 60, status));
+            ui.add(egui::ProgressBar::new(player.gain()).desired_width(200.0).text("Volume"));
+            ui.separator();
+
+            ui.add(egui::Slider::new(&mut player.track_length, 0.0..=600.0).suffix(" s").text("Track length (0 loops forever)"));
+            ui.add(egui::Slider::new(&mut player.fade_length, 0.0..=30.0).suffix(" s").text("Fade out"));
+            if ui.button("Fade out now").clicked() {
+                player.fade_out();
+            }
+        });
+        self.open = open;
+
+        if let Some(track) = play {
+            if let Err(error) = rusty.play_track(track) {
+                println!("Failed to play track {}: {}", track + 1, error);
+            }
+        }
+    }
+}
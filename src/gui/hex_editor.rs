@@ -0,0 +1,183 @@
+use egui::{Color32, Context, RichText, Sense};
+use crate::rusty_boy::{region_name, MemoryView, RustyBoy};
+
+const BYTES_PER_ROW: usize = 16;
+// gui frames a changed byte stays highlighted
+const HIGHLIGHT_FRAMES: u32 = 60;
+
+// Debug window with a live hex view of the address space or a single cartridge bank
+pub struct HexEditor {
+    pub open: bool,
+    view: MemoryView,
+    // previous contents and the frame each byte last changed, for highlighting
+    snapshot: Vec<u8>,
+    changed: Vec<u32>,
+    frame: u32,
+    // selected byte offset within the view
+    selected: Option<usize>,
+    value: String,
+    goto: String,
+    scroll_to: Option<usize>,
+    error: String,
+}
+
+impl HexEditor {
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            view: MemoryView::Bus,
+            snapshot: Vec::new(),
+            changed: Vec::new(),
+            frame: 0,
+            selected: None,
+            value: String::new(),
+            goto: String::new(),
+            scroll_to: None,
+            error: String::new(),
+        }
+    }
+
+    pub fn show(&mut self, ctx: &Context, rusty: &mut RustyBoy) {
+        if !self.open {
+            return;
+        }
+        let mut open = self.open;
+        let mut write = None;
+        egui::Window::new("Memory").open(&mut open).default_height(420.0).show(ctx, |ui| {
+            if rusty.cpu().is_none() {
+                ui.label("No ROM loaded");
+                return;
+            }
+
+            // view selection, the full bus or one cartridge bank
+            let (rom_banks, ram_banks) = rusty.memory_banks();
+            let view_name = |view: MemoryView| match view {
+                MemoryView::Bus => String::from("Address space"),
+                MemoryView::Rom(bank) => format!("ROM bank {}", bank),
+                MemoryView::Ram(bank) => format!("RAM bank {}", bank),
+            };
+            let previous_view = self.view;
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_id_salt("hex_view").selected_text(view_name(self.view)).show_ui(ui, |ui| {
+                    let views = std::iter::once(MemoryView::Bus)
+                        .chain((0..rom_banks).map(MemoryView::Rom))
+                        .chain((0..ram_banks).map(MemoryView::Ram));
+                    for view in views {
+                        ui.selectable_value(&mut self.view, view, view_name(view));
+                    }
+                });
+
+                ui.label("Goto $");
+                let response = ui.add(egui::TextEdit::singleline(&mut self.goto).desired_width(40.0));
+                if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                    match u16::from_str_radix(self.goto.trim().trim_start_matches('$'), 16) {
+                        Ok(address) => {
+                            let offset = (address.wrapping_sub(self.view.base())) as usize;
+                            self.scroll_to = Some(offset);
+                            self.selected = Some(offset);
+                            self.error.clear();
+                        }
+                        Err(_) => self.error = format!("Invalid address \"{}\"", self.goto),
+                    }
+                }
+            });
+
+            let data = rusty.read_memory(self.view);
+            self.track_changes(&data, previous_view != self.view);
+            let selected = self.selected.filter(|offset| *offset < data.len());
+
+            // editing the selected byte
+            ui.horizontal(|ui| {
+                match selected {
+                    Some(offset) => {
+                        let address = self.view.base().wrapping_add(offset as u16);
+                        ui.monospace(format!("{} ${:04X} = ${:02X}", region_name(address), address, data[offset]));
+                        ui.label("New value $");
+                        let response = ui.add(egui::TextEdit::singleline(&mut self.value).desired_width(24.0));
+                        if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                            match u8::from_str_radix(self.value.trim().trim_start_matches('$'), 16) {
+                                Ok(value) => {
+                                    write = Some((offset, value));
+                                    // move on so consecutive bytes can be typed in
+                                    self.selected = Some(offset + 1);
+                                    self.value.clear();
+                                    self.error.clear();
+                                    response.request_focus();
+                                }
+                                Err(_) => self.error = format!("Invalid value \"{}\"", self.value),
+                            }
+                        }
+                    }
+                    None => {
+                        ui.label("Click a byte to edit it");
+                    }
+                }
+            });
+            if !self.error.is_empty() {
+                ui.colored_label(Color32::LIGHT_RED, &self.error);
+            }
+            ui.separator();
+
+            let row_height = ui.text_style_height(&egui::TextStyle::Monospace) + ui.spacing().item_spacing.y;
+            let rows = data.len().div_ceil(BYTES_PER_ROW);
+            let mut scroll = egui::ScrollArea::vertical().auto_shrink([false, false]);
+            if let Some(offset) = self.scroll_to.take() {
+                scroll = scroll.vertical_scroll_offset((offset / BYTES_PER_ROW) as f32 * row_height);
+            }
+            scroll.show_rows(ui, row_height, rows, |ui, range| {
+                ui.spacing_mut().item_spacing.x = 4.0;
+                for row in range {
+                    let start = row * BYTES_PER_ROW;
+                    let end = (start + BYTES_PER_ROW).min(data.len());
+                    let address = self.view.base().wrapping_add(start as u16);
+                    ui.horizontal(|ui| {
+                        ui.monospace(RichText::new(format!("{:<4}", region_name(address))).color(Color32::GRAY));
+                        ui.monospace(format!("{:04X}", address));
+                        for offset in start..end {
+                            let mut text = RichText::new(format!("{:02X}", data[offset])).monospace();
+                            let age = self.frame.wrapping_sub(self.changed[offset]);
+                            if age < HIGHLIGHT_FRAMES {
+                                // fade out over the highlight time
+                                let alpha = 255 - (age * 255 / HIGHLIGHT_FRAMES) as u8;
+                                text = text.background_color(Color32::from_rgba_unmultiplied(200, 60, 60, alpha));
+                            }
+                            if selected == Some(offset) {
+                                text = text.background_color(Color32::from_rgb(60, 90, 160)).color(Color32::WHITE);
+                            }
+                            if ui.add(egui::Label::new(text).sense(Sense::click())).clicked() {
+                                self.selected = Some(offset);
+                                self.value.clear();
+                            }
+                        }
+                        let ascii: String = data[start..end]
+                            .iter()
+                            .map(|b| if b.is_ascii_graphic() { *b as char } else { '.' })
+                            .collect();
+                        ui.monospace(ascii);
+                    });
+                }
+            });
+        });
+        self.open = open;
+
+        if let Some((offset, value)) = write {
+            rusty.write_memory(self.view, offset, value);
+        }
+    }
+
+    // Marks bytes that differ from the previous frame, starting over when the view changes
+    fn track_changes(&mut self, data: &[u8], reset: bool) {
+        self.frame = self.frame.wrapping_add(1);
+        if reset || self.snapshot.len() != data.len() {
+            self.snapshot = data.to_vec();
+            self.changed = vec![self.frame.wrapping_sub(HIGHLIGHT_FRAMES); data.len()];
+            return;
+        }
+        for (offset, byte) in data.iter().enumerate() {
+            if self.snapshot[offset] != *byte {
+                self.snapshot[offset] = *byte;
+                self.changed[offset] = self.frame;
+            }
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use egui::load::SizedTexture;
+use egui::{pos2, vec2, Color32, Context, Image, Rect, RichText, TextureId};
+use egui_sdl2_gl::painter::Painter;
+use crate::gui::update_texture;
+use crate::rusty_boy::RustyBoy;
+
+// all 40 sprite previews side by side, 8 x 16 each
+const ATLAS_WIDTH: usize = 40 * 8;
+const ATLAS_HEIGHT: usize = 16;
+const PREVIEW_SCALE: f32 = 3.0;
+
+// Debug window listing the 40 oam entries
+pub struct OamViewer {
+    pub open: bool,
+    // scanline used to mark the entries dropped by the 10 sprite limit
+    line: u8,
+    // keep the scanline on the live LY until the user picks one
+    follow_ly: bool,
+    texture: Option<TextureId>,
+    // hovered sprite area in screen pixels (x, y, height) for the main screen overlay
+    pub highlight: Option<(i32, i32, i32)>,
+}
+
+impl OamViewer {
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            line: 0,
+            follow_ly: true,
+            texture: None,
+            highlight: None,
+        }
+    }
+
+    pub fn show(&mut self, ctx: &Context, painter: &mut Painter, rusty: &RustyBoy) {
+        self.highlight = None;
+        let mut open = self.open;
+        egui::Window::new("OAM").open(&mut open).default_height(500.0).show(ctx, |ui| {
+            let Some(screen) = rusty.screen() else {
+                ui.label("No ROM loaded");
+                return;
+            };
+
+            if self.follow_ly {
+                // LY runs up to 153 during vblank, past the last visible line
+                self.line = screen.ly.min(143);
+            }
+            ui.horizontal(|ui| {
+                if ui.add(egui::Slider::new(&mut self.line, 0..=143).text("Scanline")).changed() {
+                    self.follow_ly = false;
+                }
+                ui.checkbox(&mut self.follow_ly, "Follow LY");
+            });
+            let (visible, dropped) = screen.sprites_on_line(self.line);
+            let height = if screen.lcdc.sprite_height { 16 } else { 8 };
+            let oam = screen.oam;
+
+            let mut atlas = vec![0; ATLAS_WIDTH * ATLAS_HEIGHT * 4];
+            for entry in 0..40 {
+                let sprite = screen.render_sprite(entry);
+                for row in 0..ATLAS_HEIGHT {
+                    let dst = (row * ATLAS_WIDTH + entry * 8) * 4;
+                    atlas[dst..dst + 32].copy_from_slice(&sprite[row * 32..row * 32 + 32]);
+                }
+            }
+            drop(screen);
+            let texture = update_texture(painter, &mut self.texture, (ATLAS_WIDTH, ATLAS_HEIGHT), atlas);
+
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                egui::Grid::new("oam_grid").striped(true).show(ui, |ui| {
+                    for header in ["#", "Sprite", "Y", "X", "Tile", "Attr", "Flip", "Palette", "Priority"] {
+                        ui.strong(header);
+                    }
+                    ui.strong(format!("Line {}", self.line));
+                    ui.end_row();
+
+                    for entry in 0..40 {
+                        let [y, x, tile, attr] = [oam[entry * 4], oam[entry * 4 + 1], oam[entry * 4 + 2], oam[entry * 4 + 3]];
+                        let mut hovered = ui.label(format!("{}", entry)).hovered();
+
+                        let uv = Rect::from_min_max(
+                            pos2(entry as f32 * 8.0 / ATLAS_WIDTH as f32, 0.0),
+                            pos2((entry + 1) as f32 * 8.0 / ATLAS_WIDTH as f32, height as f32 / ATLAS_HEIGHT as f32),
+                        );
+                        let size = vec2(8.0, height as f32) * PREVIEW_SCALE;
+                        let preview = ui.add(Image::new(SizedTexture::new(texture, size)).uv(uv).bg_fill(Color32::DARK_GRAY));
+                        hovered |= preview.hovered();
+
+                        ui.label(format!("{}", y));
+                        ui.label(format!("{}", x));
+                        ui.label(format!("${:02X}", tile));
+                        ui.label(format!("%{:08b}", attr));
+                        let flip = match ((attr >> 5) & 1 != 0, (attr >> 6) & 1 != 0) {
+                            (true, true) => "X Y",
+                            (true, false) => "X",
+                            (false, true) => "Y",
+                            (false, false) => "-",
+                        };
+                        ui.label(flip);
+                        ui.label(if attr & 0b10000 != 0 { "OBP1" } else { "OBP0" });
+                        ui.label(if attr & 0x80 != 0 { "Behind BG" } else { "Above BG" });
+                        if visible.contains(&entry) {
+                            ui.label(RichText::new("Drawn").color(Color32::LIGHT_GREEN));
+                        } else if dropped.contains(&entry) {
+                            ui.label(RichText::new("Dropped").color(Color32::LIGHT_RED));
+                        } else {
+                            ui.label("-");
+                        }
+                        ui.end_row();
+
+                        if hovered {
+                            self.highlight = Some((x as i32 - 8, y as i32 - 16, height));
+                        }
+                    }
+                });
+            });
+        });
+        self.open = open;
+    }
+}
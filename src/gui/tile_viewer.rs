@@ -0,0 +1,77 @@
+use egui::load::SizedTexture;
+use egui::{vec2, Context, Image, TextureId};
+use egui_sdl2_gl::painter::Painter;
+use crate::gui::update_texture;
+use crate::rusty_boy::{RustyBoy, ViewPalette};
+
+// 16 x 24 tiles of 8 x 8 pixels
+const WIDTH: usize = 128;
+const HEIGHT: usize = 192;
+const SCALE: f32 = 3.0;
+
+// Debug window showing all 384 tiles in vram
+pub struct TileViewer {
+    pub open: bool,
+    palette: ViewPalette,
+    texture: Option<TextureId>,
+}
+
+impl TileViewer {
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            palette: ViewPalette::Bgp,
+            texture: None,
+        }
+    }
+
+    pub fn show(&mut self, ctx: &Context, painter: &mut Painter, rusty: &RustyBoy) {
+        let mut open = self.open;
+        egui::Window::new("Tile data").open(&mut open).resizable(false).show(ctx, |ui| {
+            let Some(screen) = rusty.screen() else {
+                ui.label("No ROM loaded");
+                return;
+            };
+
+            ui.horizontal(|ui| {
+                ui.label("Palette");
+                ui.radio_value(&mut self.palette, ViewPalette::Bgp, "BGP");
+                ui.radio_value(&mut self.palette, ViewPalette::Obp0, "OBP0");
+                ui.radio_value(&mut self.palette, ViewPalette::Obp1, "OBP1");
+                ui.radio_value(&mut self.palette, ViewPalette::Grey, "Grey");
+            });
+
+            // redrawn every frame so it follows the game live
+            let rgba = screen.render_tile_data(self.palette);
+            let unsigned_mode = screen.lcdc.tiledata_select;
+            drop(screen);
+            let texture = update_texture(painter, &mut self.texture, (WIDTH, HEIGHT), rgba);
+
+            let size = vec2(WIDTH as f32 * SCALE, HEIGHT as f32 * SCALE);
+            let response = ui.add(Image::new(SizedTexture::new(texture, size)));
+            if let Some(pos) = response.hover_pos() {
+                let local = (pos - response.rect.min) / SCALE;
+                let tile = (local.y as usize / 8).min(23) * 16 + (local.x as usize / 8).min(15);
+                response.on_hover_ui_at_pointer(|ui| {
+                    ui.label(tile_info(tile, unsigned_mode));
+                });
+            }
+        });
+        self.open = open;
+    }
+}
+
+// Describes a tile's address and which LCDC.4 addressing mode can reach it
+fn tile_info(tile: usize, unsigned_mode: bool) -> String {
+    let address = 0x8000 + tile * 16;
+    let (modes, index) = match tile {
+        0..=127 => ("$8000 mode (sprites, BG/window with LCDC.4 = 1)", tile as i32),
+        128..=255 => ("$8000 and $8800 modes (sprites and BG/window)", tile as i32),
+        _ => ("$8800 mode (BG/window with LCDC.4 = 0)", tile as i32 - 256),
+    };
+    let active = if unsigned_mode { "$8000" } else { "$8800" };
+    format!(
+        "Tile {} (${:02X})\nAddress ${:04X}\nUsed by {}\nBG/window currently use {} mode",
+        tile, index as u8, address, modes, active
+    )
+}
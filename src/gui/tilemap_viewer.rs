@@ -0,0 +1,107 @@
+use egui::load::SizedTexture;
+use egui::{pos2, vec2, Color32, Context, Image, Rect, Stroke, StrokeKind, TextureId};
+use egui_sdl2_gl::painter::Painter;
+use crate::gui::update_texture;
+use crate::rusty_boy::{RustyBoy, ViewPalette};
+
+// 32 x 32 tiles of 8 x 8 pixels
+const SIZE: usize = 256;
+const SCALE: f32 = 2.0;
+
+// Debug window showing the $9800 and $9C00 tile maps with the scroll viewport and window area
+pub struct TilemapViewer {
+    pub open: bool,
+    high_map: bool,
+    palette: ViewPalette,
+    texture: Option<TextureId>,
+}
+
+impl TilemapViewer {
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            high_map: false,
+            palette: ViewPalette::Bgp,
+            texture: None,
+        }
+    }
+
+    pub fn show(&mut self, ctx: &Context, painter: &mut Painter, rusty: &RustyBoy) {
+        let mut open = self.open;
+        egui::Window::new("Tile maps").open(&mut open).resizable(false).show(ctx, |ui| {
+            let Some(screen) = rusty.screen() else {
+                ui.label("No ROM loaded");
+                return;
+            };
+
+            ui.horizontal(|ui| {
+                ui.label("Map");
+                ui.radio_value(&mut self.high_map, false, "$9800");
+                ui.radio_value(&mut self.high_map, true, "$9C00");
+                ui.separator();
+                ui.label("Palette");
+                ui.radio_value(&mut self.palette, ViewPalette::Bgp, "BGP");
+                ui.radio_value(&mut self.palette, ViewPalette::Grey, "Grey");
+            });
+
+            let bg_map = screen.lcdc.backgroundmap_select;
+            let window_map = screen.lcdc.windowmap_select;
+            let window_enable = screen.lcdc.window_enable;
+            let (scx, scy, wx, wy) = (screen.scx, screen.scy, screen.wx, screen.wy);
+            ui.label(format!(
+                "SCX {} SCY {}   WX {} WY {}   BG map ${}   Window map ${}{}",
+                scx, scy, wx, wy,
+                if bg_map { "9C00" } else { "9800" },
+                if window_map { "9C00" } else { "9800" },
+                if window_enable { "" } else { " (off)" },
+            ));
+
+            let rgba = screen.render_tilemap(self.high_map, self.palette);
+            let tiledata_select = screen.lcdc.tiledata_select;
+            let map = screen.vram[if self.high_map { 0x1C00 } else { 0x1800 }..][..0x400].to_vec();
+            drop(screen);
+            let texture = update_texture(painter, &mut self.texture, (SIZE, SIZE), rgba);
+
+            let response = ui.add(Image::new(SizedTexture::new(texture, vec2(SIZE as f32 * SCALE, SIZE as f32 * SCALE))));
+            let origin = response.rect.min;
+            let overlay = ui.painter().with_clip_rect(response.rect);
+
+            // scroll viewport, wraps around the 256 x 256 map
+            if self.high_map == bg_map {
+                let stroke = Stroke::new(2.0, Color32::RED);
+                for (dx, dy) in [(0.0, 0.0), (-256.0, 0.0), (0.0, -256.0), (-256.0, -256.0)] {
+                    let min = pos2(scx as f32 + dx, scy as f32 + dy);
+                    let rect = Rect::from_min_size(origin + min.to_vec2() * SCALE, vec2(160.0, 144.0) * SCALE);
+                    overlay.rect_stroke(rect, 0.0, stroke, StrokeKind::Inside);
+                }
+            }
+
+            // window area starts at the map origin and covers the screen from WX - 7, WY
+            if self.high_map == window_map && window_enable && wx <= 166 && wy <= 143 {
+                let size = vec2(167.0 - wx as f32, 144.0 - wy as f32);
+                let rect = Rect::from_min_size(origin, size * SCALE);
+                overlay.rect_stroke(rect, 0.0, Stroke::new(2.0, Color32::LIGHT_BLUE), StrokeKind::Inside);
+            }
+
+            if let Some(pos) = response.hover_pos() {
+                let local = (pos - origin) / SCALE;
+                let (map_x, map_y) = ((local.x as usize / 8).min(31), (local.y as usize / 8).min(31));
+                let index = map[map_y * 32 + map_x];
+                let base = if self.high_map { 0x9C00 } else { 0x9800 };
+                // tile data address of this index in the current LCDC.4 mode
+                let tile_address = if tiledata_select {
+                    0x8000 + index as usize * 16
+                } else {
+                    (0x9000 + (index as i8) as isize * 16) as usize
+                };
+                response.on_hover_ui_at_pointer(|ui| {
+                    ui.label(format!(
+                        "Map ({}, {}) at ${:04X}\nTile ${:02X} at ${:04X}",
+                        map_x, map_y, base + map_y * 32 + map_x, index, tile_address
+                    ));
+                });
+            }
+        });
+        self.open = open;
+    }
+}
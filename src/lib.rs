@@ -0,0 +1,2 @@
+// Emulator core without the window and gui, used by the app and by headless tests
+pub mod rusty_boy;
@@ -1,18 +1,26 @@
-mod rusty_boy;
+mod gui;
+use rustyboy::rusty_boy;
 use egui_sdl2_gl::{gl, DpiScaling, ShaderVersion};
+use std::path::Path;
 use std::time::{Duration, Instant};
-use egui::{vec2, Direction, FullOutput, Image};
+use std::collections::HashMap;
+use egui::{vec2, Direction, FullOutput, Image, TextureId};
 use egui::load::SizedTexture;
 use sdl2::event::Event;
 use sdl2::image::LoadSurface;
 use sdl2::keyboard::Keycode;
 use sdl2::surface::Surface;
 use sdl2::video::{GLProfile, SwapInterval};
-use crate::rusty_boy::RustyBoy;
+use crate::gui::{AudioPanel, AudioVisualizer, DebuggerWindow, GbsPlayerWindow, HexEditor, OamViewer, TileViewer, TilemapViewer};
+use crate::rusty_boy::{AudioLatency, AudioModel, Layer, RustyBoy};
+use crate::rusty_boy::filter::Filter;
+use crate::rusty_boy::recorder::{RecordFormat, VgmOptions, WavFormat, WavSource};
 
 // Gameboy size constant
 const GB_WIDTH: usize = 160;
 const GB_HEIGHT: usize = 144;
+// default gdb remote port
+const GDB_PORT: u16 = 2159;
 fn main() {
     
     // init gameboy 
@@ -63,14 +71,32 @@ fn main() {
     // set screen buffer placeholder
     let mut screen_buffer = rusty.update_and_render();
 
-    // texture
-    let gb_texture = painter.new_user_texture_rgba8((GB_WIDTH, GB_HEIGHT), screen_buffer, false);
+    // textures, one per filter output scale since a texture can't be resized
+    let mut gb_textures: HashMap<usize, TextureId> = HashMap::new();
+    gb_textures.insert(1, painter.new_user_texture_rgba8((GB_WIDTH, GB_HEIGHT), screen_buffer, false));
+    let mut filter = Filter::Nearest;
     let start_time = Instant::now();
 
     // counter 
     let mut counter = 0;
     let mut frame_counter = Instant::now();
 
+    // screenshot integer scale
+    let mut screenshot_scale: usize = 1;
+    let mut wav_source = WavSource::Mix;
+    let mut wav_format = WavFormat::Pcm16;
+    let mut vgm_options = VgmOptions::new();
+
+    // debug windows
+    let mut tile_viewer = TileViewer::new();
+    let mut tilemap_viewer = TilemapViewer::new();
+    let mut oam_viewer = OamViewer::new();
+    let mut debugger_window = DebuggerWindow::new();
+    let mut hex_editor = HexEditor::new();
+    let mut audio_panel = AudioPanel::new();
+    let mut audio_visualizer = AudioVisualizer::new();
+    let mut gbs_player = GbsPlayerWindow::new();
+
     // frame
     let my_frame = egui::containers::Frame::new().fill(egui::Color32::DARK_GRAY);
     
@@ -84,6 +110,8 @@ fn main() {
                     match keycode {
                         // Escape to quit
                         Some(Keycode::ESCAPE) => break 'running,
+                        // F12 to take a screenshot
+                        Some(Keycode::F12) => save_screenshot(&rusty, screenshot_scale),
                         _ => rusty.handle_events(keycode, false)
                     }
                 }
@@ -123,8 +151,13 @@ fn main() {
             gl::Clear(gl::COLOR_BUFFER_BIT);
         }
         
-        // update screen buffer
-        painter.update_user_texture_rgba8_data(gb_texture, screen_buffer);
+        // filter and update screen buffer
+        let scale = filter.scale();
+        let filtered = filter.apply(GB_WIDTH, GB_HEIGHT, &screen_buffer);
+        let gb_texture = *gb_textures.entry(scale).or_insert_with(|| {
+            painter.new_user_texture_rgba8((GB_WIDTH * scale, GB_HEIGHT * scale), filtered.clone(), false)
+        });
+        painter.update_user_texture_rgba8_data(gb_texture, filtered);
 
         // GUI layout
         egui::CentralPanel::default().frame(my_frame)
@@ -135,18 +168,212 @@ fn main() {
                         if ui.add_sized([80.0, 20.0], egui::Button::new("Load ROM")).clicked() {
                             if let Some(path) = rfd::FileDialog::new().pick_file() {
                                 rusty.load_rom(path);
+                                // gbs files open the music player
+                                gbs_player.open |= rusty.gbs().is_some();
                             }
                         }
-                    })
+                    });
+                    col[1].vertical_centered(|ui| {
+                        ui.menu_button("Options", |ui| {
+                            let mut restrictions = rusty.access_restrictions();
+                            if ui.checkbox(&mut restrictions, "VRAM/OAM access restrictions").changed() {
+                                rusty.set_access_restrictions(restrictions);
+                            }
+                            ui.add(egui::Slider::new(&mut screenshot_scale, 1..=8).text("Screenshot scale"));
+                            if ui.button("Screenshot (F12)").clicked() {
+                                save_screenshot(&rusty, screenshot_scale);
+                                ui.close_menu();
+                            }
+                            let mut ghosting = rusty.ghosting();
+                            if ui.add(egui::Slider::new(&mut ghosting, 0.0..=0.95).text("LCD ghosting")).changed() {
+                                rusty.set_ghosting(ghosting);
+                            }
+                            ui.menu_button("Filter", |ui| {
+                                for option in Filter::ALL {
+                                    ui.radio_value(&mut filter, option, option.name());
+                                }
+                            });
+                            ui.menu_button("Audio model", |ui| {
+                                let mut model = rusty.audio_model();
+                                for option in AudioModel::ALL {
+                                    ui.radio_value(&mut model, option, option.name());
+                                }
+                                if model != rusty.audio_model() {
+                                    rusty.set_audio_model(model);
+                                }
+                            });
+                            ui.menu_button("Audio latency", |ui| {
+                                let mut latency = rusty.audio_latency();
+                                for option in AudioLatency::ALL {
+                                    ui.radio_value(&mut latency, option, option.name());
+                                }
+                                if latency != rusty.audio_latency() {
+                                    rusty.set_audio_latency(latency);
+                                }
+                                if let Some(sample_rate) = rusty.audio_sample_rate() {
+                                    ui.label(format!("Output {} Hz", sample_rate));
+                                }
+                            });
+                            ui.menu_button("Palette", |ui| {
+                                let mut theme = rusty.theme();
+                                for (i, name) in rusty.theme_names().iter().enumerate() {
+                                    ui.radio_value(&mut theme, i, name);
+                                }
+                                if theme != rusty.theme() {
+                                    rusty.set_theme(theme);
+                                }
+                                ui.separator();
+                                if ui.button("Load palette file...").clicked() {
+                                    if let Some(path) = rfd::FileDialog::new().add_filter("Palette", &["json"]).pick_file() {
+                                        if let Err(error) = rusty.load_themes(&path) {
+                                            println!("Failed to load palette file: {}", error);
+                                        }
+                                    }
+                                    ui.close_menu();
+                                }
+                            });
+                        });
+                    });
+                    col[2].vertical_centered(|ui| {
+                        ui.menu_button("Record", |ui| {
+                            if rusty.is_recording() {
+                                if ui.button("Stop recording").clicked() {
+                                    rusty.stop_recording();
+                                    ui.close_menu();
+                                }
+                            } else {
+                                for (label, format) in [("Record AVI", RecordFormat::Avi), ("Record GIF", RecordFormat::Gif)] {
+                                    if ui.button(label).clicked() {
+                                        match rusty.start_recording(Path::new("recordings"), format) {
+                                            Ok(path) => println!("Recording to {}", path.display()),
+                                            Err(error) => println!("Failed to start recording: {}", error),
+                                        }
+                                        ui.close_menu();
+                                    }
+                                }
+                            }
+                            ui.separator();
+                            if rusty.is_wav_recording() {
+                                if ui.button("Stop WAV recording").clicked() {
+                                    rusty.stop_wav_recording();
+                                    ui.close_menu();
+                                }
+                            } else {
+                                for option in WavSource::ALL {
+                                    ui.radio_value(&mut wav_source, option, option.name());
+                                }
+                                for option in WavFormat::ALL {
+                                    ui.radio_value(&mut wav_format, option, option.name());
+                                }
+                                if ui.button("Record WAV").clicked() {
+                                    match rusty.start_wav_recording(Path::new("recordings"), wav_source, wav_format) {
+                                        Ok(paths) => {
+                                            for path in paths {
+                                                println!("Recording audio to {}", path.display());
+                                            }
+                                        }
+                                        Err(error) => println!("Failed to start audio recording: {}", error),
+                                    }
+                                    ui.close_menu();
+                                }
+                            }
+                            ui.separator();
+                            if rusty.is_vgm_recording() {
+                                if ui.button("Mark loop start").clicked() {
+                                    rusty.mark_vgm_loop();
+                                    ui.close_menu();
+                                }
+                                if ui.button("Stop VGM recording").clicked() {
+                                    rusty.stop_vgm_recording();
+                                    ui.close_menu();
+                                }
+                            } else {
+                                ui.checkbox(&mut vgm_options.detect_loop, "Detect loop");
+                                ui.add_enabled(
+                                    vgm_options.detect_loop,
+                                    egui::Slider::new(&mut vgm_options.min_loop_secs, 1.0..=120.0).suffix(" s").text("Shortest loop"),
+                                );
+                                if ui.button("Record VGM").clicked() {
+                                    match rusty.start_vgm_recording(Path::new("recordings"), vgm_options) {
+                                        Ok(path) => println!("Logging sound registers to {}", path.display()),
+                                        Err(error) => println!("Failed to start vgm recording: {}", error),
+                                    }
+                                    ui.close_menu();
+                                }
+                            }
+                        });
+                    });
+                    col[3].vertical_centered(|ui| {
+                        ui.menu_button("Debug", |ui| {
+                            ui.checkbox(&mut tile_viewer.open, "Tile data");
+                            ui.checkbox(&mut tilemap_viewer.open, "Tile maps");
+                            ui.checkbox(&mut oam_viewer.open, "OAM");
+                            ui.checkbox(&mut debugger_window.open, "Debugger");
+                            ui.checkbox(&mut hex_editor.open, "Memory");
+                            ui.checkbox(&mut audio_panel.open, "Audio");
+                            ui.checkbox(&mut audio_visualizer.open, "Audio visualizer");
+                            ui.checkbox(&mut gbs_player.open, "GBS player");
+                            ui.separator();
+                            let status = rusty.gdb_status();
+                            let mut listening = status.is_some();
+                            let label = match status {
+                                Some((port, true)) => format!("GDB server (port {}, attached)", port),
+                                Some((port, false)) => format!("GDB server (port {})", port),
+                                None => String::from("GDB server"),
+                            };
+                            if ui.checkbox(&mut listening, label).changed() {
+                                if listening {
+                                    match rusty.start_gdb(GDB_PORT) {
+                                        Ok(port) => println!("GDB server listening on 127.0.0.1:{}", port),
+                                        Err(error) => println!("Failed to start GDB server: {}", error),
+                                    }
+                                } else {
+                                    rusty.stop_gdb();
+                                }
+                            }
+                            ui.menu_button("Layers", |ui| {
+                                egui::Grid::new("layer_grid").show(ui, |ui| {
+                                    for layer in Layer::ALL {
+                                        let mut options = rusty.layer(layer);
+                                        ui.label(layer.name());
+                                        ui.checkbox(&mut options.visible, "Show");
+                                        ui.checkbox(&mut options.tint, "Tint");
+                                        ui.end_row();
+                                        if options != rusty.layer(layer) {
+                                            rusty.set_layer(layer, options);
+                                        }
+                                    }
+                                });
+                            });
+                        });
+                    });
                 });
                 ui.end_row();
                 ui.add(egui::Separator::default().spacing(5.0));
                 ui.with_layout(egui::Layout::centered_and_justified(Direction::LeftToRight), |ui| {
                     // Actual emulator screen
-                    ui.add(Image::new(SizedTexture::new(gb_texture, vec2((GB_WIDTH * 3) as f32, (GB_HEIGHT * 3) as f32))))
+                    let response = ui.add(Image::new(SizedTexture::new(gb_texture, vec2((GB_WIDTH * 3) as f32, (GB_HEIGHT * 3) as f32))));
+
+                    // outline the sprite hovered in the oam viewer
+                    if let Some((x, y, height)) = oam_viewer.highlight {
+                        let min = response.rect.min + vec2(x as f32, y as f32) * 3.0;
+                        let rect = egui::Rect::from_min_size(min, vec2(8.0, height as f32) * 3.0);
+                        ui.painter().with_clip_rect(response.rect)
+                            .rect_stroke(rect, 0.0, egui::Stroke::new(2.0, egui::Color32::RED), egui::StrokeKind::Outside);
+                    }
                 });
             });
 
+        // debug windows
+        tile_viewer.show(&egui_ctx, &mut painter, &rusty);
+        tilemap_viewer.show(&egui_ctx, &mut painter, &rusty);
+        oam_viewer.show(&egui_ctx, &mut painter, &rusty);
+        debugger_window.show(&egui_ctx, &mut rusty);
+        hex_editor.show(&egui_ctx, &mut rusty);
+        audio_panel.show(&egui_ctx, &mut rusty);
+        audio_visualizer.show(&egui_ctx, &mut rusty);
+        gbs_player.show(&egui_ctx, &mut rusty);
+
         let FullOutput {
             platform_output,
             textures_delta,
@@ -163,5 +390,13 @@ fn main() {
         painter.paint_jobs(None, textures_delta, paint_jobs);
         window.gl_swap_window();
     }
+    // dropping rusty finishes any recording in progress
 }
 
+// saves a screenshot into the screenshots folder of the working directory
+fn save_screenshot(rusty: &RustyBoy, scale: usize) {
+    match rusty.save_screenshot(Path::new("screenshots"), scale) {
+        Ok(path) => println!("Saved screenshot to {}", path.display()),
+        Err(error) => println!("Failed to save screenshot: {}", error),
+    }
+}
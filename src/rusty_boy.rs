@@ -1,31 +1,290 @@
 use crate::rusty_boy::cpu::CPU;
+use crate::rusty_boy::debugger::{Debugger, Instruction, Watchpoint};
+use crate::rusty_boy::cartridge::Cartridge;
+use crate::rusty_boy::gbs::GbsPlayer;
+use crate::rusty_boy::gdb::GdbServer;
+use crate::rusty_boy::recorder::{RecordFormat, Recorder, VgmOptions, WavFormat, WavRecorder, WavSource};
+use crate::rusty_boy::screen::Screen;
+use crate::rusty_boy::screen::theme::Theme;
+use crate::rusty_boy::symbols::Symbols;
+use std::cell::Ref;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 use sdl2::keyboard::Keycode;
 mod cartridge;
 mod cpu;
+pub mod debugger;
+pub mod filter;
+pub mod gbs;
+mod gdb;
 mod joypad;
 mod memory;
+pub use memory::{region_name, MemoryView};
 mod motherboard;
+pub mod png;
+pub mod recorder;
 mod screen;
+mod symbols;
+pub use screen::{Layer, LayerOptions, ViewPalette};
 mod timer;
 mod sound;
+pub use sound::{AudioLatency, AudioModel, ChannelStatus, Mixer, RECORD_SAMPLE_RATE};
 
 pub struct RustyBoy {
-    cpu: Option<CPU>
+    cpu: Option<CPU>,
+    // vram/oam access restrictions accuracy option
+    access_restrictions: bool,
+    // palette themes, presets followed by user loaded ones
+    themes: Vec<Theme>,
+    theme: usize,
+    // lcd ghosting persistence, 0 is off
+    ghosting: f32,
+    // gameplay recorder, None when not recording
+    recorder: Option<Recorder>,
+    // debug layer switches, indexed by Layer
+    layers: [LayerOptions; 3],
+    // breakpoints and stepping, kept across ROM loads
+    debugger: Debugger,
+    // memory watchpoints, mirrored into the cpu memory
+    watchpoints: Vec<Watchpoint>,
+    // gdb remote server, None when not listening
+    gdb: Option<GdbServer>,
+    // apu analog output stage model
+    audio_model: AudioModel,
+    // buffering between the apu and the output device
+    audio_latency: AudioLatency,
+    // channel mute, solo and gain
+    mixer: Mixer,
+    // wav audio recorder, None when not recording
+    wav_recorder: Option<WavRecorder>,
+    // vgm output file and its loop options, None when not logging
+    vgm: Option<(PathBuf, VgmOptions)>,
+    // music player state while a gbs file is loaded
+    gbs: Option<GbsPlayer>,
 }
 
 impl RustyBoy {
     // Init with None as CPU
     pub fn new() -> Self {
         Self {
-            cpu: None
+            cpu: None,
+            access_restrictions: false,
+            themes: Theme::presets(),
+            theme: 0,
+            ghosting: 0.0,
+            recorder: None,
+            layers: [LayerOptions::new(); 3],
+            debugger: Debugger::new(),
+            watchpoints: Vec::new(),
+            gdb: None,
+            audio_model: AudioModel::Dmg,
+            audio_latency: AudioLatency::Normal,
+            mixer: Mixer::new(),
+            wav_recorder: None,
+            vgm: None,
+            gbs: None,
         }
     }
-    // Inits a new cpu with Rom
+    // Inits a new cpu with Rom, .gbs files start the music player instead
     pub fn load_rom(&mut self, rom_file: PathBuf) {
+        self.stop_recording();
+        self.stop_wav_recording();
+        self.stop_vgm_recording();
+        if rom_file.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("gbs")) {
+            if let Err(error) = self.load_gbs(&rom_file) {
+                println!("Failed to load {}: {}", rom_file.display(), error);
+            }
+            return;
+        }
+        self.gbs = None;
         let bytes: Vec<u8> = fs::read(&rom_file).unwrap();
-        self.cpu = Some(CPU::new(bytes));
+        let mut cpu = CPU::new(bytes);
+        self.apply_settings(&mut cpu);
+        // rgbds symbols next to the rom
+        let sym_file = rom_file.with_extension("sym");
+        if sym_file.exists() {
+            match Symbols::load(&sym_file) {
+                Ok(symbols) => cpu.symbols = symbols,
+                Err(error) => println!("Failed to load {}: {}", sym_file.display(), error),
+            }
+        }
+        self.debugger.resume();
+        self.cpu = Some(cpu);
+    }
+
+    // Carries the emulator settings over to a new cpu
+    fn apply_settings(&self, cpu: &mut CPU) {
+        cpu.memory.access_restrictions = self.access_restrictions;
+        cpu.motherboard.screen.borrow_mut().set_theme(&self.themes[self.theme]);
+        cpu.motherboard.screen.borrow_mut().ghosting = self.ghosting;
+        cpu.motherboard.screen.borrow_mut().layers = self.layers;
+        cpu.memory.watchpoints = self.watchpoints.clone();
+        cpu.motherboard.audio.borrow_mut().set_model(self.audio_model);
+        if let Err(error) = cpu.motherboard.audio.borrow_mut().set_latency(self.audio_latency) {
+            println!("Failed to set audio latency: {}", error);
+        }
+        cpu.motherboard.audio.borrow_mut().set_mixer(self.mixer);
+    }
+
+    // Loads a gbs music file and starts playing its first song
+    pub fn load_gbs(&mut self, path: &Path) -> Result<(), String> {
+        let player = GbsPlayer::load(path)?;
+        let track = player.track();
+        self.gbs = Some(player);
+        self.play_track(track)
+    }
+    // Music player state, None unless a gbs file is loaded
+    pub fn gbs(&self) -> Option<&GbsPlayer> {
+        self.gbs.as_ref()
+    }
+    pub fn gbs_mut(&mut self) -> Option<&mut GbsPlayer> {
+        self.gbs.as_mut()
+    }
+    // Restarts the machine on a gbs track, 0 based. The machine is reset in place, so the audio
+    // output stays open between tracks
+    pub fn play_track(&mut self, track: usize) -> Result<(), String> {
+        let player = self.gbs.as_mut().ok_or("No GBS file loaded")?;
+        let cartridge = Cartridge::gbs(player.start(track))?;
+        self.stop_recording();
+        self.stop_wav_recording();
+        self.stop_vgm_recording();
+        let mut cpu = match self.cpu.take() {
+            Some(mut cpu) => {
+                cpu.restart(cartridge);
+                cpu
+            }
+            None => CPU::with_cartridge(cartridge),
+        };
+        self.apply_settings(&mut cpu);
+        self.debugger.resume();
+        self.cpu = Some(cpu);
+        Ok(())
+    }
+
+    pub fn access_restrictions(&self) -> bool {
+        self.access_restrictions
+    }
+    // toggles vram/oam access restrictions, applies to the running game as well
+    pub fn set_access_restrictions(&mut self, enabled: bool) {
+        self.access_restrictions = enabled;
+        if let Some(cpu) = &mut self.cpu {
+            cpu.memory.access_restrictions = enabled;
+        }
+    }
+    
+    pub fn theme_names(&self) -> Vec<String> {
+        self.themes.iter().map(|theme| theme.name.clone()).collect()
+    }
+    pub fn theme(&self) -> usize {
+        self.theme
+    }
+    // switches palette theme without resetting the game
+    pub fn set_theme(&mut self, index: usize) {
+        if index >= self.themes.len() {
+            return;
+        }
+        self.theme = index;
+        if let Some(cpu) = &mut self.cpu {
+            cpu.motherboard.screen.borrow_mut().set_theme(&self.themes[index]);
+        }
+    }
+    // adds user themes from a palette file and switches to the first one
+    pub fn load_themes(&mut self, path: &Path) -> Result<(), String> {
+        let themes = Theme::load(path)?;
+        let first = self.themes.len();
+        self.themes.extend(themes);
+        self.set_theme(first);
+        Ok(())
+    }
+    
+    pub fn ghosting(&self) -> f32 {
+        self.ghosting
+    }
+    // sets how much of the previous frame persists, 0 disables frame blending
+    pub fn set_ghosting(&mut self, persistence: f32) {
+        self.ghosting = persistence.clamp(0.0, 0.95);
+        if let Some(cpu) = &mut self.cpu {
+            cpu.motherboard.screen.borrow_mut().ghosting = self.ghosting;
+        }
+    }
+    
+    pub fn audio_model(&self) -> AudioModel {
+        self.audio_model
+    }
+    // switches the high pass filter model of the audio output
+    pub fn set_audio_model(&mut self, model: AudioModel) {
+        self.audio_model = model;
+        if let Some(cpu) = &mut self.cpu {
+            cpu.motherboard.audio.borrow_mut().set_model(model);
+        }
+    }
+
+    pub fn audio_latency(&self) -> AudioLatency {
+        self.audio_latency
+    }
+    // reopens the audio output with a new buffer size, keeps the old output if the device fails.
+    // Recordings run at RECORD_SAMPLE_RATE, so a device with another rate doesn't affect them
+    pub fn set_audio_latency(&mut self, latency: AudioLatency) {
+        self.audio_latency = latency;
+        let Some(cpu) = &mut self.cpu else {
+            return;
+        };
+        if let Err(error) = cpu.motherboard.audio.borrow_mut().set_latency(latency) {
+            println!("Failed to set audio latency: {}", error);
+        }
+    }
+    // sample rate of the output device, None when no ROM is loaded
+    pub fn audio_sample_rate(&self) -> Option<u32> {
+        self.cpu.as_ref().map(|cpu| cpu.motherboard.audio.borrow().sample_rate())
+    }
+
+    pub fn mixer(&self) -> Mixer {
+        self.mixer
+    }
+    pub fn set_mixer(&mut self, mixer: Mixer) {
+        self.mixer = mixer;
+        if let Some(cpu) = &mut self.cpu {
+            cpu.motherboard.audio.borrow_mut().set_mixer(mixer);
+        }
+    }
+    // per channel peak levels since the last call, for meters
+    pub fn audio_levels(&mut self) -> [f32; 4] {
+        match &mut self.cpu {
+            Some(cpu) => cpu.motherboard.audio.borrow_mut().take_levels(),
+            None => [0.0; 4],
+        }
+    }
+
+    // Keeps the channel dac output history for the oscilloscope while enabled
+    pub fn set_audio_scope(&mut self, enabled: bool) {
+        if let Some(cpu) = &mut self.cpu {
+            cpu.motherboard.audio.borrow_mut().set_scope(enabled);
+        }
+    }
+    // Channel dac outputs since the last call, one point every 16 cycles
+    pub fn take_audio_scope(&mut self) -> Vec<[f32; 4]> {
+        match &mut self.cpu {
+            Some(cpu) => cpu.motherboard.audio.borrow_mut().take_scope(),
+            None => Vec::new(),
+        }
+    }
+    // Decoded channel registers and the ch3 wave ram, None when no ROM is loaded
+    pub fn audio_status(&self) -> Option<([ChannelStatus; 4], [u8; 16])> {
+        let cpu = self.cpu.as_ref()?;
+        let audio = cpu.motherboard.audio.borrow();
+        Some((audio.channel_status(), audio.wave_ram()))
+    }
+
+    pub fn layer(&self, layer: Layer) -> LayerOptions {
+        self.layers[layer as usize]
+    }
+    // hides or tints a single layer, takes effect on the next scanline
+    pub fn set_layer(&mut self, layer: Layer, options: LayerOptions) {
+        self.layers[layer as usize] = options;
+        if let Some(cpu) = &mut self.cpu {
+            cpu.motherboard.screen.borrow_mut().layers = self.layers;
+        }
     }
     
     // handle events
@@ -58,16 +317,316 @@ impl RustyBoy {
             None => {}
         }
     }
+    // Encodes the current frame as a PNG, integer scaled
+    pub fn screenshot_png(&self, scale: usize) -> Option<Vec<u8>> {
+        let cpu = self.cpu.as_ref()?;
+        let screen = cpu.motherboard.screen.borrow();
+        let scaled = png::scale_rgba(160, 144, &screen.screen_buffer, scale);
+        Some(png::encode_png(160 * scale.max(1), 144 * scale.max(1), &scaled))
+    }
+    // Saves the current frame to a timestamped PNG inside folder, returns the file path
+    pub fn save_screenshot(&self, folder: &Path, scale: usize) -> Result<PathBuf, String> {
+        let bytes = self.screenshot_png(scale).ok_or("No ROM loaded")?;
+        fs::create_dir_all(folder).map_err(|e| e.to_string())?;
+        let path = folder.join(format!("rustyboy_{}.png", timestamp()));
+        fs::write(&path, bytes).map_err(|e| e.to_string())?;
+        Ok(path)
+    }
+
+    // Starts recording into a timestamped file inside folder, returns the file path
+    pub fn start_recording(&mut self, folder: &Path, format: RecordFormat) -> Result<PathBuf, String> {
+        self.stop_recording();
+        let cpu = self.cpu.as_ref().ok_or("No ROM loaded")?;
+        fs::create_dir_all(folder).map_err(|e| e.to_string())?;
+        let path = folder.join(format!("rustyboy_{}.{}", timestamp(), format.extension()));
+        let recorder = Recorder::new(&path, format)?;
+        cpu.motherboard.audio.borrow_mut().set_capture(recorder.records_audio());
+        self.recorder = Some(recorder);
+        Ok(path)
+    }
+    // Stops and finalizes the current recording, if any
+    pub fn stop_recording(&mut self) {
+        if let Some(recorder) = self.recorder.take() {
+            if let Some(cpu) = &self.cpu {
+                cpu.motherboard.audio.borrow_mut().set_capture(false);
+            }
+            if let Err(error) = recorder.finish() {
+                println!("Failed to finish recording: {}", error);
+            }
+        }
+    }
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+
+    // Starts recording audio into timestamped wav files inside folder, returns the file paths.
+    // Recording starts and stops exactly between two instructions, always at RECORD_SAMPLE_RATE whatever the device uses
+    pub fn start_wav_recording(&mut self, folder: &Path, source: WavSource, format: WavFormat) -> Result<Vec<PathBuf>, String> {
+        self.stop_wav_recording();
+        let cpu = self.cpu.as_ref().ok_or("No ROM loaded")?;
+        fs::create_dir_all(folder).map_err(|e| e.to_string())?;
+        let base = folder.join(format!("rustyboy_{}", timestamp()));
+        let recorder = WavRecorder::new(&base, source, format, RECORD_SAMPLE_RATE)?;
+        cpu.motherboard.audio.borrow_mut().set_wav_capture(true, source == WavSource::Stems);
+        let paths = recorder.paths();
+        self.wav_recorder = Some(recorder);
+        Ok(paths)
+    }
+    // Stops and finalizes the wav recording, if any
+    pub fn stop_wav_recording(&mut self) {
+        if let Some(mut recorder) = self.wav_recorder.take() {
+            if let Some(cpu) = &self.cpu {
+                let mut audio = cpu.motherboard.audio.borrow_mut();
+                let samples = audio.take_wav_capture();
+                audio.set_wav_capture(false, false);
+                if let Err(error) = recorder.add_samples(&samples) {
+                    println!("Failed to write wav samples: {}", error);
+                }
+            }
+            if let Err(error) = recorder.finish() {
+                println!("Failed to finish wav recording: {}", error);
+            }
+        }
+    }
+    pub fn is_wav_recording(&self) -> bool {
+        self.wav_recorder.is_some()
+    }
+
+    // Starts logging apu register writes for a timestamped vgm file inside folder, returns the file path.
+    // The file is written when the recording stops
+    pub fn start_vgm_recording(&mut self, folder: &Path, options: VgmOptions) -> Result<PathBuf, String> {
+        self.stop_vgm_recording();
+        let cpu = self.cpu.as_ref().ok_or("No ROM loaded")?;
+        fs::create_dir_all(folder).map_err(|e| e.to_string())?;
+        let path = folder.join(format!("rustyboy_{}.vgm", timestamp()));
+        cpu.motherboard.audio.borrow_mut().start_vgm_log();
+        self.vgm = Some((path.clone(), options));
+        Ok(path)
+    }
+    // Marks the loop start of the vgm recording at the current time
+    pub fn mark_vgm_loop(&mut self) {
+        if let Some(cpu) = &self.cpu {
+            cpu.motherboard.audio.borrow_mut().mark_vgm_loop();
+        }
+    }
+    // Stops logging and writes the vgm file, if recording
+    pub fn stop_vgm_recording(&mut self) {
+        if let Some((path, options)) = self.vgm.take() {
+            let Some(log) = self.cpu.as_ref().and_then(|cpu| cpu.motherboard.audio.borrow_mut().take_vgm_log()) else {
+                return;
+            };
+            if let Err(error) = log.save(&path, options) {
+                println!("Failed to write {}: {}", path.display(), error);
+            }
+        }
+    }
+    pub fn is_vgm_recording(&self) -> bool {
+        self.vgm.is_some()
+    }
+
+    // Read access to the ppu for the debug viewers, None when no ROM is loaded
+    pub fn screen(&self) -> Option<Ref<'_, Screen>> {
+        self.cpu.as_ref().map(|cpu| {
+            // the tile, tilemap and oam views read the cache
+            cpu.motherboard.screen.borrow_mut().update_tile_cache();
+            cpu.motherboard.screen.borrow()
+        })
+    }
+
+    // Read access to the cpu for the debugger window, None when no ROM is loaded
+    pub fn cpu(&self) -> Option<&CPU> {
+        self.cpu.as_ref()
+    }
+    pub fn debugger(&self) -> &Debugger {
+        &self.debugger
+    }
+    pub fn debugger_mut(&mut self) -> &mut Debugger {
+        &mut self.debugger
+    }
+    // Number of cartridge rom and ram banks for the hex editor
+    pub fn memory_banks(&self) -> (usize, usize) {
+        match &self.cpu {
+            Some(cpu) => (cpu.memory.cartridge.rom.len().div_ceil(0x4000), cpu.memory.cartridge.ram.len().div_ceil(0x2000)),
+            None => (0, 0),
+        }
+    }
+    pub fn read_memory(&self, view: MemoryView) -> Vec<u8> {
+        match &self.cpu {
+            Some(cpu) => cpu.memory.read_view(view),
+            None => Vec::new(),
+        }
+    }
+    pub fn write_memory(&mut self, view: MemoryView, offset: usize, value: u8) {
+        if let Some(cpu) = &mut self.cpu {
+            cpu.memory.write_view(view, offset, value);
+        }
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+    // replaces the watchpoint list, an empty list turns the memory checks off
+    pub fn set_watchpoints(&mut self, watchpoints: Vec<Watchpoint>) {
+        self.watchpoints = watchpoints;
+        if let Some(cpu) = &mut self.cpu {
+            cpu.memory.watchpoints = self.watchpoints.clone();
+        }
+    }
+    // Starts listening for gdb on a localhost port, 0 picks a free one, returns the port
+    pub fn start_gdb(&mut self, port: u16) -> Result<u16, String> {
+        self.stop_gdb();
+        let server = GdbServer::bind(port)?;
+        let port = server.port();
+        self.gdb = Some(server);
+        Ok(port)
+    }
+    // Closes the server, a paused game keeps running
+    pub fn stop_gdb(&mut self) {
+        if let Some(server) = self.gdb.take() {
+            if server.connected() {
+                self.debugger.resume();
+            }
+        }
+    }
+    // Listening port and whether a client is attached
+    pub fn gdb_status(&self) -> Option<(u16, bool)> {
+        self.gdb.as_ref().map(|server| (server.port(), server.connected()))
+    }
+    // Bank and address of a symbol from the .sym file
+    pub fn lookup_symbol(&self, name: &str) -> Option<(u16, u16)> {
+        self.cpu.as_ref()?.symbols.lookup(name)
+    }
+    // Disassembly around pc, up to before instructions ahead of it and after from it on
+    pub fn disassembly(&self, before: usize, after: usize) -> Vec<Instruction> {
+        let Some(cpu) = &self.cpu else {
+            return Vec::new();
+        };
+        let pc = cpu.registers.pc;
+
+        // instructions can't be decoded backwards, so walk forward from further back
+        // and keep the first start that lines up with pc
+        let mut lines = Vec::new();
+        for back in (1..=before as u16 * 3).rev() {
+            let mut offset = 0;
+            let mut list = Vec::new();
+            while offset < back {
+                let instruction = cpu.disassemble(pc.wrapping_sub(back - offset));
+                offset += instruction.bytes.len() as u16;
+                list.push(instruction);
+            }
+            if offset == back {
+                lines = list;
+                break;
+            }
+        }
+        lines.drain(..lines.len().saturating_sub(before));
+
+        let mut address = pc;
+        for _ in 0..after {
+            let instruction = cpu.disassemble(address);
+            address = instruction.next();
+            lines.push(instruction);
+        }
+        lines
+    }
+    // steps over the call at pc, or a single instruction otherwise
+    pub fn step_over(&mut self) {
+        if let Some(cpu) = &self.cpu {
+            let instruction = cpu.disassemble(cpu.registers.pc);
+            self.debugger.step_over(&instruction, cpu.registers.sp);
+        }
+    }
+    // runs until the current function returns, without a known caller it just resumes
+    pub fn step_out(&mut self) {
+        if let Some(cpu) = &self.cpu {
+            self.debugger.step_out(cpu.call_stack.len());
+        }
+    }
+
     // Returns screen buffer vector
     pub fn update_and_render(&mut self) -> Vec<u8> {
-        match &mut self.cpu {
+        let mut next_track = None;
+        let screen_buffer = match &mut self.cpu {
             None => {
                 [0xff; 160 * 144 * 4].to_vec()
             },
             Some(cpu) => {
-                cpu.run_one_frame();
-                cpu.motherboard.screen.borrow().screen_buffer.clone()
+                if let Some(server) = &mut self.gdb {
+                    server.poll(cpu, &mut self.debugger);
+                }
+                let finished = cpu.run_one_frame(&mut self.debugger);
+                let screen_buffer = cpu.motherboard.screen.borrow().screen_buffer.clone();
+
+                // gbs playback time, fade out and moving on to the next song
+                if let (true, Some(player)) = (finished, &mut self.gbs) {
+                    let gain = player.advance_frame();
+                    cpu.motherboard.audio.borrow_mut().set_fade(gain);
+                    if player.finished() && player.track() + 1 < player.track_count() {
+                        next_track = Some(player.track() + 1);
+                    }
+                }
+
+                let wav_samples = match self.wav_recorder {
+                    Some(_) => cpu.motherboard.audio.borrow_mut().take_wav_capture(),
+                    None => Vec::new(),
+                };
+
+                // feed the recorder the finished frame and the audio produced during it
+                if let (true, Some(recorder)) = (finished, &mut self.recorder) {
+                    let samples = cpu.motherboard.audio.borrow_mut().take_capture();
+                    if let Err(error) = recorder.add_frame(&screen_buffer, &samples) {
+                        println!("Recording stopped: {}", error);
+                        self.stop_recording();
+                    }
+                }
+
+                // write out the audio recorded during the frame
+                if let Some(recorder) = &mut self.wav_recorder {
+                    if let Err(error) = recorder.add_samples(&wav_samples) {
+                        println!("Wav recording stopped: {}", error);
+                        self.stop_wav_recording();
+                    }
+                }
+                screen_buffer
+            }
+        };
+        if let Some(track) = next_track {
+            if let Err(error) = self.play_track(track) {
+                println!("Failed to play track {}: {}", track + 1, error);
             }
         }
+        screen_buffer
+    }
+}
+
+// finishes recordings still running, so closing the app never leaves a file with placeholder headers
+impl Drop for RustyBoy {
+    fn drop(&mut self) {
+        self.stop_recording();
+        self.stop_wav_recording();
+        self.stop_vgm_recording();
     }
 }
+
+// UTC timestamp for file names, YYYYMMDD_HHMMSS_mmm
+fn timestamp() -> String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = now.as_secs();
+    let (hour, minute, second) = (secs / 3600 % 24, secs / 60 % 60, secs % 60);
+
+    // days since epoch to civil date
+    let days = (secs / 86400) as i64 + 719468;
+    let era = days / 146097;
+    let doe = days - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+
+    format!(
+        "{:04}{:02}{:02}_{:02}{:02}{:02}_{:03}",
+        year, month, day, hour, minute, second, now.subsec_millis()
+    )
+}
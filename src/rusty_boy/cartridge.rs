@@ -4,7 +4,7 @@ mod cartridge_header;
 mod mbc;
 mod rtc;
 
-use mbc::{MBCType, MBC0, MBC1, MBC3};
+use mbc::{MBCGbs, MBCType, MBC0, MBC1, MBC3};
 
 const RAM_SIZE: &[usize] = &[
     0,
@@ -62,11 +62,36 @@ impl Cartridge {
         Ok(cartridge)
     }
     
+    // GBS player image, header checksums don't matter and the mapper is the minimal gbs one
+    pub fn gbs(rom: Vec<u8>) -> Result<Self, String> {
+        let header = match CartridgeHeader::from_bytes(&rom) {
+            Ok(header) | Err((Some(header), _)) => header,
+            Err((None, error)) => return Err(error),
+        };
+        let rom_banks = rom.len().div_ceil(0x4000) as u16;
+        Ok(Self {
+            header,
+            rom,
+            ram: vec![0; 0x2000],
+            mbc: MBCType::GBS(MBCGbs::new(rom_banks)),
+        })
+    }
+
     pub fn read(&self, address: u16) -> u8 {
         match &self.mbc {
             MBCType::MBC0(x) => x.read(address, &self.rom, &self.ram),
             MBCType::MBC1(x) => x.read(address, &self.rom, &self.ram),
             MBCType::MBC3(x) => x.read(address, &self.rom, &self.ram),
+            MBCType::GBS(x) => x.read(address, &self.rom, &self.ram),
+        }
+    }
+    // currently mapped rom or ram bank for a cartridge address
+    pub fn bank(&self, address: u16) -> u16 {
+        match &self.mbc {
+            MBCType::MBC0(x) => x.bank(address),
+            MBCType::MBC1(x) => x.bank(address),
+            MBCType::MBC3(x) => x.bank(address),
+            MBCType::GBS(x) => x.bank(address),
         }
     }
     pub fn write(&mut self, address: u16, value: u8) {
@@ -74,6 +99,7 @@ impl Cartridge {
             MBCType::MBC0(x) => x.write(address, value, &self.rom, &mut self.ram),
             MBCType::MBC1(x) => x.write(address, value, &self.rom, &mut self.ram),
             MBCType::MBC3(x) => x.write(address, value, &self.rom, &mut self.ram),
+            MBCType::GBS(x) => x.write(address, value, &self.rom, &mut self.ram),
         }
     }
 }
@@ -6,7 +6,8 @@ use crate::rusty_boy::cartridge::rtc::RTC;
 pub enum MBCType {
     MBC0(MBC0),
     MBC1(MBC1),
-    MBC3(MBC3)
+    MBC3(MBC3),
+    GBS(MBCGbs),
 }
 
 // MBC0 memory controller
@@ -20,19 +21,30 @@ impl MBC0 {
         }
         // ram read
         else if c!(0xa000 <= address < 0xc000) {
-            ram[address as usize - 0xa000]
+            if ram.is_empty() {
+                return 0xff
+            }
+            ram[(address as usize - 0xa000) % ram.len()]
         }
         else {
             unreachable!()
         }
     }
 
+    // bank mapped at an address, for the debugger
+    pub fn bank(&self, address: u16) -> u16 {
+        if c!(0x4000 <= address < 0x8000) { 1 } else { 0 }
+    }
+
     pub fn write(&self, address: u16, value: u8, rom: &[u8], ram: &mut [u8]) {
         // Rom write (shouldnt be possible)
         if address < 0x8000 {}
         // ram write
         else if c!(0xa000 <= address < 0xc000) {
-            ram[address as usize - 0xa000] = value
+            if !ram.is_empty() {
+                let len = ram.len();
+                ram[(address as usize - 0xa000) % len] = value
+            }
         }
         else {
             unreachable!()
@@ -68,6 +80,16 @@ impl MBC1 {
         let lower_bank = if self.mode {self.rom_bank & 0b1100000} else {0} as u16;
         (lower_bank, mod_bank)
     }
+    // bank mapped at an address, for the debugger
+    pub fn bank(&self, address: u16) -> u16 {
+        if address < 0x4000 {
+            self.get_banks().0 % self.total_rom_banks
+        } else if c!(0x4000 <= address < 0x8000) {
+            self.get_banks().1
+        } else {
+            self.get_banks().0 >> 5
+        }
+    }
     pub fn read(&self, address: u16, rom: &[u8], ram: &[u8]) -> u8 {
         // lower cartridge bank read
         if address < 0x4000 {
@@ -163,6 +185,16 @@ impl MBC3 {
             ram_enabled: false,
         }
     }
+    // bank mapped at an address, for the debugger
+    pub fn bank(&self, address: u16) -> u16 {
+        if address < 0x4000 {
+            0
+        } else if c!(0x4000 <= address < 0x8000) {
+            (self.rom_bank as usize % self.total_rom_banks as usize) as u16
+        } else {
+            self.ram_bank as u16
+        }
+    }
     pub fn read(&self, address: u16, rom: &[u8], ram: &[u8]) -> u8 {
         if address < 0x4000 {
             rom[address as usize]
@@ -242,4 +274,46 @@ impl MBC3 {
             }
         }
     }
-}
\ No newline at end of file
+}
+// GBS music rip mapper, a plain rom bank register at 2000-3fff and always enabled ram
+#[derive(PartialEq,Clone,Debug,Eq)]
+pub struct MBCGbs {
+    rom_bank: u16,
+    total_rom_banks: u16,
+}
+
+impl MBCGbs {
+    pub fn new(t_rom_banks: u16) -> Self {
+        Self {
+            rom_bank: 1,
+            total_rom_banks: t_rom_banks,
+        }
+    }
+    // bank mapped at an address, for the debugger
+    pub fn bank(&self, address: u16) -> u16 {
+        if c!(0x4000 <= address < 0x8000) { self.rom_bank } else { 0 }
+    }
+    pub fn read(&self, address: u16, rom: &[u8], ram: &[u8]) -> u8 {
+        if address < 0x4000 {
+            rom[address as usize]
+        }
+        else if c!(0x4000 <= address < 0x8000) {
+            rom[self.rom_bank as usize * 0x4000 + (address as usize - 0x4000)]
+        }
+        else if c!(0xa000 <= address < 0xc000) {
+            ram[address as usize - 0xa000]
+        }
+        else {
+            unreachable!()
+        }
+    }
+    pub fn write(&mut self, address: u16, value: u8, _rom: &[u8], ram: &mut [u8]) {
+        // bank 0 maps bank 1 like the usual mappers, also when the number wraps around to 0
+        if c!(0x2000 <= address < 0x4000) {
+            self.rom_bank = (value as u16 % self.total_rom_banks).max(1);
+        }
+        else if c!(0xa000 <= address < 0xc000) {
+            ram[address as usize - 0xa000] = value;
+        }
+    }
+}
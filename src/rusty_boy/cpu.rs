@@ -1,35 +1,61 @@
 mod registers;
+use crate::rusty_boy::cartridge::Cartridge;
+use crate::rusty_boy::debugger::{self, CallFrame, Debugger, Instruction};
 use crate::rusty_boy::memory::Memory;
 use crate::rusty_boy::motherboard::Motherboard;
-use registers::Registers;
+use crate::rusty_boy::symbols::Symbols;
+pub use registers::Registers;
 use serde_json::Value;
 use std::{fs, process};
 use std::rc::Rc;
 
+// tracked call frames, the oldest are dropped beyond this
+const MAX_CALL_DEPTH: usize = 64;
+
 pub struct CPU {
     pub registers: Registers,
-    memory: Memory,
+    pub memory: Memory,
     pub motherboard: Rc<Motherboard>,
     opcode_table: Value,
     i_queue: bool,
     halt: bool,
     blargg: String,
+    // labels from the rom's .sym file
+    pub symbols: Symbols,
+    // calls that haven't returned yet, innermost last
+    pub call_stack: Vec<CallFrame>,
 }
 
 impl CPU {
     pub fn new(rom_file: Vec<u8>) -> Self {
+        Self::with_cartridge(Cartridge::new(rom_file).unwrap())
+    }
+
+    // Starts up with an already built cartridge, e.g. a gbs player image
+    pub fn with_cartridge(cartridge: Cartridge) -> Self {
+        Self::on_motherboard(cartridge, Motherboard::new())
+    }
+
+    // Restarts on another cartridge in place, the motherboard is reset so the audio output stays open
+    pub fn restart(&mut self, cartridge: Cartridge) {
+        self.motherboard.reset();
+        *self = Self::on_motherboard(cartridge, Rc::clone(&self.motherboard));
+    }
+
+    fn on_motherboard(cartridge: Cartridge, mobo: Rc<Motherboard>) -> Self {
         let opcodedata = fs::read_to_string("./src/rusty_boy/opcodes/Opcodes.json").unwrap();
         // let rom: Vec<u8> = fs::read(rom_file).unwrap();
-        let mobo = Motherboard::new();
         // Initialize self
         let mut this = Self {
             registers: Registers::new(),
-            memory: Memory::new(rom_file, &mobo),
+            memory: Memory::new(cartridge, &mobo),
             opcode_table: serde_json::from_str(&opcodedata).unwrap(),
             motherboard: mobo,
             i_queue: false,
             halt: false,
             blargg: String::new(),
+            symbols: Symbols::new(),
+            call_stack: Vec::new(),
         };
         
         // Init memory values
@@ -234,21 +260,37 @@ impl CPU {
         self.registers.pc = address;
     }
     fn ret(&mut self) {
+        // drop the returning frame along with any the code abandoned by moving sp
+        let sp = self.registers.sp;
+        while self.call_stack.last().is_some_and(|frame| frame.sp <= sp) {
+            self.call_stack.pop();
+        }
         let address = self.pop_stack();
         self.jp_to(address);
     }
     fn call(&mut self, value: u16) {
-        self.push_stack(self.registers.pc);
+        let return_address = self.registers.pc;
+        self.push_stack(return_address);
         self.jp_to(value);
+        if self.call_stack.len() == MAX_CALL_DEPTH {
+            self.call_stack.remove(0);
+        }
+        self.call_stack.push(CallFrame { target: value, return_address, sp: self.registers.sp });
     }
     // pops stack to reg u16
     fn pop_reg(&mut self, reg: &str) {
+        // sp increments twice, each can corrupt oam
+        self.memory.oam_bug(self.registers.sp, true);
+        self.memory.oam_bug(self.registers.sp.wrapping_add(1), true);
         let value = self.pop_stack();
         self.registers.set_u16_reg(reg, value).unwrap();
     }
     // pushes reg to stack u16
     fn push_reg(&mut self, reg: &str) {
         let value = self.registers.get_u16_reg(reg).unwrap();
+        // sp decrements twice, each can corrupt oam
+        self.memory.oam_bug(self.registers.sp, false);
+        self.memory.oam_bug(self.registers.sp.wrapping_sub(1), false);
         self.push_stack(value);
     }
     // a == reg
@@ -444,7 +486,9 @@ impl CPU {
             self.registers.set_flag("h", (val & 0xF) == 0x0).unwrap();
         } else {
             // 16 bit
-            let val = self.registers.get_u16_reg(reg).unwrap() + 1;
+            let val = self.registers.get_u16_reg(reg).unwrap();
+            self.memory.oam_bug(val, false);
+            let val = val + 1;
             self.registers.set_u16_reg(reg, val).unwrap();
         }
     }
@@ -458,7 +502,9 @@ impl CPU {
             self.registers.set_flag("h", val & 0xF == 0xF).unwrap();
         } else {
             // 16 bit
-            let val = self.registers.get_u16_reg(reg).unwrap() - 1;
+            let val = self.registers.get_u16_reg(reg).unwrap();
+            self.memory.oam_bug(val, false);
+            let val = val - 1;
             self.registers.set_u16_reg(reg, val).unwrap();
         }
     }
@@ -1416,21 +1462,75 @@ impl CPU {
         let mem2 = self.memory.get(pc + 1);
         let mem3 = self.memory.get(pc + 2);
         let mem4 = self.memory.get(pc + 3);
-        format!(
+        let mut log = format!(
             "A:{:02x} F:{:02x} B:{:02x} C:{:02x} D:{:02x} E:{:02x} H:{:02x} L:{:02x} SP:{:04x} PC:{:04x} PCMEM:{:02x},{:02x},{:02x},{:02x}",
             a, f, b, c, d, e, h, l, sp, pc, mem1, mem2, mem3, mem4
-        )
+        );
+        // symbol suffix, only when a .sym file was loaded so plain traces stay comparable
+        if let Some(label) = self.describe_address(pc) {
+            log.push_str(&format!(" ; {}", label));
+        }
+        log
+    }
+    
+    // Disassembles the instruction at an address for the debugger
+    pub fn disassemble(&self, address: u16) -> Instruction {
+        let mut opcode = self.memory.peek(address);
+        let mut table = "unprefixed";
+        if opcode == 0xCB {
+            opcode = self.memory.peek(address.wrapping_add(1));
+            table = "cbprefixed";
+        }
+        let entry = &self.opcode_table[table][format!("{:#04X}", opcode)];
+        let length = entry["bytes"].as_u64().unwrap_or(1) as u16;
+        let bytes = (0..length).map(|i| self.memory.peek(address.wrapping_add(i))).collect();
+        let mut instruction = debugger::format_instruction(address, bytes, entry);
+        if (0x4000..0x8000).contains(&address) {
+            instruction.bank = Some(self.memory.bank(address));
+        }
+        if !self.symbols.is_empty() {
+            instruction.label = self.symbol(address).map(String::from);
+            // show the operand address as its label
+            if let Some(name) = instruction.target.and_then(|target| self.symbol(target)) {
+                instruction.text = instruction.text.replace(&format!("${:04X}", instruction.target.unwrap()), name);
+            }
+        }
+        instruction
     }
     
-    // runs for one full frame
-    pub fn run_one_frame(&mut self) {
+    // Label at an address, looked up in the bank currently mapped there
+    pub fn symbol(&self, address: u16) -> Option<&str> {
+        self.symbols.label(self.memory.bank(address), address)
+    }
+    // Nearest label for an address as "label+offset", using the mapped bank
+    pub fn describe_address(&self, address: u16) -> Option<String> {
+        self.symbols.describe(self.memory.bank(address), address)
+    }
+    
+    // runs for one full frame, returns false when the debugger stopped it early
+    pub fn run_one_frame(&mut self, debugger: &mut Debugger) -> bool {
+        let watching = !self.memory.watchpoints.is_empty();
         // loop until a frame is finished
         loop {
+            if debugger.active() && debugger.should_break(&self.registers, &self.memory) {
+                return false;
+            }
+            
+            let pc = self.registers.pc;
             self.update();
 
+            if watching {
+                if let Some(hit) = self.memory.watch_hit.take() {
+                    debugger.watch_hit(hit, pc);
+                }
+            }
+            if debugger.active() {
+                debugger.after_instruction(&self.registers, self.call_stack.len());
+            }
+
             if self.motherboard.screen.borrow().frame_done {
                 self.motherboard.screen.borrow_mut().frame_done = false;
-                break;
+                return true;
             }
         }
     }
@@ -0,0 +1,391 @@
+// Breakpoints, watchpoints, stepping and disassembly on top of CPU::update
+use crate::rusty_boy::cpu::Registers;
+use crate::rusty_boy::memory::Memory;
+use serde_json::Value;
+
+// Registers a breakpoint condition can test
+#[derive(PartialEq, Clone, Copy, Debug, Eq)]
+pub enum Register {
+    A, F, B, C, D, E, H, L,
+    AF, BC, DE, HL, SP, PC,
+}
+
+impl Register {
+    pub const ALL: [Register; 14] = [
+        Register::A, Register::F, Register::B, Register::C, Register::D, Register::E, Register::H, Register::L,
+        Register::AF, Register::BC, Register::DE, Register::HL, Register::SP, Register::PC,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Register::A => "A",
+            Register::F => "F",
+            Register::B => "B",
+            Register::C => "C",
+            Register::D => "D",
+            Register::E => "E",
+            Register::H => "H",
+            Register::L => "L",
+            Register::AF => "AF",
+            Register::BC => "BC",
+            Register::DE => "DE",
+            Register::HL => "HL",
+            Register::SP => "SP",
+            Register::PC => "PC",
+        }
+    }
+
+    pub fn read(&self, registers: &Registers) -> u16 {
+        let name = self.name().to_lowercase();
+        match name.len() {
+            1 => registers.get_u8_reg(&name).unwrap() as u16,
+            _ => registers.get_u16_reg(&name).unwrap(),
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Copy, Debug, Eq)]
+pub enum Compare {
+    Equal,
+    NotEqual,
+    Less,
+    Greater,
+}
+
+impl Compare {
+    pub const ALL: [Compare; 4] = [Compare::Equal, Compare::NotEqual, Compare::Less, Compare::Greater];
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Compare::Equal => "==",
+            Compare::NotEqual => "!=",
+            Compare::Less => "<",
+            Compare::Greater => ">",
+        }
+    }
+}
+
+// Register test that has to pass for a breakpoint to hit
+#[derive(PartialEq, Clone, Copy, Debug, Eq)]
+pub struct Condition {
+    pub register: Register,
+    pub compare: Compare,
+    pub value: u16,
+}
+
+impl Condition {
+    pub fn test(&self, registers: &Registers) -> bool {
+        let current = self.register.read(registers);
+        match self.compare {
+            Compare::Equal => current == self.value,
+            Compare::NotEqual => current != self.value,
+            Compare::Less => current < self.value,
+            Compare::Greater => current > self.value,
+        }
+    }
+}
+
+impl std::fmt::Display for Condition {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} {} ${:X}", self.register.name(), self.compare.symbol(), self.value)
+    }
+}
+
+#[derive(PartialEq, Clone, Copy, Debug, Eq)]
+pub struct Breakpoint {
+    pub address: u16,
+    // only hits while this rom bank is mapped, None for any bank
+    pub bank: Option<u16>,
+    pub condition: Option<Condition>,
+    pub enabled: bool,
+}
+
+impl Breakpoint {
+    fn hits(&self, registers: &Registers, memory: &Memory) -> bool {
+        self.enabled
+            && self.address == registers.pc
+            && self.bank.map_or(true, |bank| memory.bank(self.address) == bank)
+            && self.condition.map_or(true, |c| c.test(registers))
+    }
+}
+
+// Call made by CALL, RST or an interrupt, tracked by the cpu for the call stack view
+#[derive(PartialEq, Clone, Copy, Debug, Eq)]
+pub struct CallFrame {
+    pub target: u16,
+    pub return_address: u16,
+    // stack pointer right after the return address was pushed
+    pub sp: u16,
+}
+
+#[derive(PartialEq, Clone, Copy, Debug, Eq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    // a write that changed the stored value
+    Change,
+}
+
+impl WatchKind {
+    pub const ALL: [WatchKind; 3] = [WatchKind::Read, WatchKind::Write, WatchKind::Change];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            WatchKind::Read => "Read",
+            WatchKind::Write => "Write",
+            WatchKind::Change => "Change",
+        }
+    }
+}
+
+// Memory watchpoint over an inclusive address range
+#[derive(PartialEq, Clone, Copy, Debug, Eq)]
+pub struct Watchpoint {
+    pub start: u16,
+    pub end: u16,
+    pub kind: WatchKind,
+    pub enabled: bool,
+}
+
+// Access that triggered a watchpoint, recorded by Memory
+#[derive(PartialEq, Clone, Copy, Debug, Eq)]
+pub struct WatchHit {
+    pub address: u16,
+    pub kind: WatchKind,
+    pub old: u8,
+    pub new: u8,
+    // cartridge bank for rom and cartridge ram addresses
+    pub bank: Option<u16>,
+}
+
+// Decoded instruction for the disassembly view
+#[derive(Clone, Debug)]
+pub struct Instruction {
+    pub address: u16,
+    // rom bank for switchable bank addresses
+    pub bank: Option<u16>,
+    pub bytes: Vec<u8>,
+    pub mnemonic: String,
+    // mnemonic with formatted operands
+    pub text: String,
+    // address operand or jump target, for symbol lookups
+    pub target: Option<u16>,
+    // symbol at the instruction address
+    pub label: Option<String>,
+}
+
+impl Instruction {
+    // address of the instruction that follows this one
+    pub fn next(&self) -> u16 {
+        self.address.wrapping_add(self.bytes.len() as u16)
+    }
+}
+
+// Formats an instruction from its Opcodes.json entry and raw bytes
+pub fn format_instruction(address: u16, bytes: Vec<u8>, entry: &Value) -> Instruction {
+    let mnemonic = entry["mnemonic"].as_str().unwrap_or("???").to_string();
+    let end = address.wrapping_add(bytes.len() as u16);
+    // immediate data is at the end of the instruction, little endian
+    let imm8 = *bytes.last().unwrap_or(&0);
+    let imm16 = if bytes.len() >= 3 { u16::from_le_bytes([bytes[1], bytes[2]]) } else { 0 };
+    let signed = |value: u8| {
+        let value = value as i8;
+        format!("{}${:02X}", if value < 0 { "-" } else { "" }, value.unsigned_abs())
+    };
+
+    let mut target = None;
+    let mut operands: Vec<String> = Vec::new();
+    let list = entry["operands"].as_array().cloned().unwrap_or_default();
+    let mut i = 0;
+    while i < list.len() {
+        let operand = &list[i];
+        let name = operand["name"].as_str().unwrap_or("");
+        let mut text = match name {
+            "d8" => format!("${:02X}", imm8),
+            "d16" => format!("${:04X}", imm16),
+            "a16" => {
+                target = Some(imm16);
+                format!("${:04X}", imm16)
+            }
+            "a8" => {
+                target = Some(0xff00 | imm8 as u16);
+                format!("$FF{:02X}", imm8)
+            }
+            // relative jumps show the target address
+            "r8" if mnemonic == "JR" => {
+                let address = end.wrapping_add(imm8 as i8 as u16);
+                target = Some(address);
+                format!("${:04X}", address)
+            }
+            "r8" => signed(imm8),
+            // rst vectors
+            _ if name.ends_with('H') => format!("${}", &name[..name.len() - 1]),
+            _ => name.to_string(),
+        };
+        if operand["increment"].as_bool().unwrap_or(false) {
+            if name == "SP" {
+                // LD HL, SP+r8 combines two operands
+                i += 1;
+                let value = imm8 as i8;
+                text = format!("SP{}${:02X}", if value < 0 { "-" } else { "+" }, value.unsigned_abs());
+            } else {
+                text.push('+');
+            }
+        }
+        if operand["decrement"].as_bool().unwrap_or(false) {
+            text.push('-');
+        }
+        if !operand["immediate"].as_bool().unwrap_or(true) {
+            text = format!("({})", text);
+        }
+        operands.push(text);
+        i += 1;
+    }
+
+    let text = if operands.is_empty() { mnemonic.clone() } else { format!("{} {}", mnemonic, operands.join(", ")) };
+    Instruction { address, bank: None, bytes, mnemonic, text, target, label: None }
+}
+
+// What the emulation loop is currently doing
+#[derive(PartialEq, Clone, Copy, Debug, Eq)]
+enum Run {
+    Running,
+    Paused,
+    // run one instruction
+    Step,
+    // run until the return address with the stack back at the saved depth
+    StepOver(u16, u16),
+    // run until the call stack is shallower than the saved depth
+    StepOut(usize),
+    RunTo(u16),
+}
+
+pub struct Debugger {
+    pub breakpoints: Vec<Breakpoint>,
+    run: Run,
+    // breakpoints at the current pc are ignored for the first instruction after resuming
+    skip_breakpoint: bool,
+    // why the emulation last paused
+    reason: String,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self {
+            breakpoints: Vec::new(),
+            run: Run::Running,
+            skip_breakpoint: false,
+            reason: String::new(),
+        }
+    }
+
+    // Checks are only needed with breakpoints set or while stepping
+    pub fn active(&self) -> bool {
+        self.run != Run::Running || !self.breakpoints.is_empty()
+    }
+    pub fn paused(&self) -> bool {
+        self.run == Run::Paused
+    }
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+
+    pub fn pause(&mut self, reason: String) {
+        self.run = Run::Paused;
+        self.reason = reason;
+    }
+    pub fn resume(&mut self) {
+        self.set_run(Run::Running);
+    }
+    pub fn step_into(&mut self) {
+        self.set_run(Run::Step);
+    }
+    // steps over calls and rsts, anything else is a single step
+    pub fn step_over(&mut self, instruction: &Instruction, sp: u16) {
+        if instruction.mnemonic == "CALL" || instruction.mnemonic == "RST" {
+            self.set_run(Run::StepOver(instruction.next(), sp));
+        } else {
+            self.set_run(Run::Step);
+        }
+    }
+    // depth is the call stack length, the step out finishes on the ret of its innermost frame
+    pub fn step_out(&mut self, depth: usize) {
+        self.set_run(Run::StepOut(depth));
+    }
+    pub fn run_to(&mut self, address: u16) {
+        self.set_run(Run::RunTo(address));
+    }
+    fn set_run(&mut self, run: Run) {
+        self.run = run;
+        self.skip_breakpoint = true;
+        self.reason.clear();
+    }
+
+    // Adds a breakpoint, or removes the unconditional one already at the address
+    pub fn toggle_breakpoint(&mut self, address: u16, bank: Option<u16>) {
+        match self.breakpoints.iter().position(|b| b.address == address && b.bank == bank && b.condition.is_none()) {
+            Some(index) => {
+                self.breakpoints.remove(index);
+            }
+            None => self.breakpoints.push(Breakpoint { address, bank, condition: None, enabled: true }),
+        }
+    }
+    pub fn has_breakpoint(&self, address: u16, bank: Option<u16>) -> bool {
+        self.breakpoints.iter().any(|b| {
+            b.enabled && b.address == address && (b.bank.is_none() || b.bank == bank)
+        })
+    }
+
+    // Called before each instruction, returns true when the emulation has to stop
+    pub fn should_break(&mut self, registers: &Registers, memory: &Memory) -> bool {
+        let pc = registers.pc;
+        let skip = std::mem::take(&mut self.skip_breakpoint);
+        match self.run {
+            Run::Paused => return true,
+            Run::StepOver(address, sp) if pc == address && registers.sp >= sp => {
+                self.pause(format!("Stepped over to ${:04X}", pc));
+                return true;
+            }
+            Run::RunTo(address) if pc == address => {
+                self.pause(format!("Ran to ${:04X}", pc));
+                return true;
+            }
+            _ => {}
+        }
+        if !skip {
+            let hit = self.breakpoints.iter().find(|b| b.hits(registers, memory));
+            if let Some(breakpoint) = hit {
+                let reason = match breakpoint.condition {
+                    Some(condition) => format!("Breakpoint at ${:04X} ({})", pc, condition),
+                    None => format!("Breakpoint at ${:04X}", pc),
+                };
+                self.pause(reason);
+                return true;
+            }
+        }
+        false
+    }
+
+    // Pauses on a watchpoint hit caused by the instruction at pc
+    pub fn watch_hit(&mut self, hit: WatchHit, pc: u16) {
+        let bank = hit.bank.map(|bank| format!(" bank {}", bank)).unwrap_or_default();
+        let access = match hit.kind {
+            WatchKind::Read => format!("read ${:02X}", hit.new),
+            WatchKind::Write | WatchKind::Change => format!("${:02X} -> ${:02X}", hit.old, hit.new),
+        };
+        self.pause(format!(
+            "{} watchpoint ${:04X}{}: {} by PC ${:04X}",
+            hit.kind.name(), hit.address, bank, access, pc
+        ));
+    }
+
+    // Called after each instruction to finish single steps and step outs
+    // call_depth is the call stack length, pushes inside the function don't count
+    pub fn after_instruction(&mut self, registers: &Registers, call_depth: usize) {
+        match self.run {
+            Run::Step => self.pause(format!("Stepped to ${:04X}", registers.pc)),
+            Run::StepOut(depth) if call_depth < depth => self.pause(format!("Stepped out to ${:04X}", registers.pc)),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,434 @@
+// Software upscaling filters applied to the RGBA8 screen buffer before it is uploaded
+
+#[derive(PartialEq, Clone, Copy, Debug, Eq)]
+pub enum Filter {
+    // no filtering, the texture is stretched with nearest sampling
+    Nearest,
+    Scale2x,
+    Scale3x,
+    Hq2x,
+    Xbrz2x,
+    // DMG dot matrix look, darkened gaps between pixels
+    LcdGrid,
+}
+
+impl Filter {
+    pub const ALL: [Filter; 6] = [
+        Filter::Nearest,
+        Filter::Scale2x,
+        Filter::Scale3x,
+        Filter::Hq2x,
+        Filter::Xbrz2x,
+        Filter::LcdGrid,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Filter::Nearest => "Nearest",
+            Filter::Scale2x => "Scale2x",
+            Filter::Scale3x => "Scale3x",
+            Filter::Hq2x => "HQ2x",
+            Filter::Xbrz2x => "xBRZ 2x",
+            Filter::LcdGrid => "LCD grid",
+        }
+    }
+
+    // output size multiplier
+    pub fn scale(&self) -> usize {
+        match self {
+            Filter::Nearest => 1,
+            Filter::Scale2x | Filter::Hq2x | Filter::Xbrz2x => 2,
+            Filter::Scale3x | Filter::LcdGrid => 3,
+        }
+    }
+
+    // Returns the filtered buffer, width * scale by height * scale
+    pub fn apply(&self, width: usize, height: usize, rgba: &[u8]) -> Vec<u8> {
+        let src = Image { width, height, rgba };
+        match self {
+            Filter::Nearest => rgba.to_vec(),
+            Filter::Scale2x => scale2x(&src),
+            Filter::Scale3x => scale3x(&src),
+            Filter::Hq2x => hq2x(&src),
+            Filter::Xbrz2x => xbrz2x(&src),
+            Filter::LcdGrid => lcd_grid(&src),
+        }
+    }
+}
+
+// Source image with clamped pixel access
+struct Image<'a> {
+    width: usize,
+    height: usize,
+    rgba: &'a [u8],
+}
+
+impl Image<'_> {
+    fn get(&self, x: isize, y: isize) -> [u8; 4] {
+        let x = x.clamp(0, self.width as isize - 1) as usize;
+        let y = y.clamp(0, self.height as isize - 1) as usize;
+        let offset = (y * self.width + x) * 4;
+        self.rgba[offset..offset + 4].try_into().unwrap()
+    }
+}
+
+// Output buffer for an integer scale of the source
+struct Output {
+    width: usize,
+    rgba: Vec<u8>,
+}
+
+impl Output {
+    fn new(src: &Image, scale: usize) -> Self {
+        Self {
+            width: src.width * scale,
+            rgba: vec![0; src.rgba.len() * scale * scale],
+        }
+    }
+    fn set(&mut self, x: usize, y: usize, color: [u8; 4]) {
+        let offset = (y * self.width + x) * 4;
+        self.rgba[offset..offset + 4].copy_from_slice(&color);
+    }
+}
+
+// Weighted mix of colors
+fn mix(colors: &[([u8; 4], u32)]) -> [u8; 4] {
+    let total: u32 = colors.iter().map(|(_, weight)| weight).sum();
+    let mut out = [0; 4];
+    for channel in 0..4 {
+        let sum: u32 = colors.iter().map(|(color, weight)| color[channel] as u32 * weight).sum();
+        out[channel] = ((sum + total / 2) / total) as u8;
+    }
+    out
+}
+
+// AdvMAME2x / EPX
+fn scale2x(src: &Image) -> Vec<u8> {
+    let mut out = Output::new(src, 2);
+    for y in 0..src.height as isize {
+        for x in 0..src.width as isize {
+            let e = src.get(x, y);
+            let b = src.get(x, y - 1);
+            let d = src.get(x - 1, y);
+            let f = src.get(x + 1, y);
+            let h = src.get(x, y + 1);
+            let (mut e0, mut e1, mut e2, mut e3) = (e, e, e, e);
+            if b != h && d != f {
+                if d == b { e0 = d; }
+                if b == f { e1 = f; }
+                if d == h { e2 = d; }
+                if h == f { e3 = f; }
+            }
+            let (ox, oy) = (x as usize * 2, y as usize * 2);
+            out.set(ox, oy, e0);
+            out.set(ox + 1, oy, e1);
+            out.set(ox, oy + 1, e2);
+            out.set(ox + 1, oy + 1, e3);
+        }
+    }
+    out.rgba
+}
+
+// AdvMAME3x
+fn scale3x(src: &Image) -> Vec<u8> {
+    let mut out = Output::new(src, 3);
+    for y in 0..src.height as isize {
+        for x in 0..src.width as isize {
+            let a = src.get(x - 1, y - 1);
+            let b = src.get(x, y - 1);
+            let c = src.get(x + 1, y - 1);
+            let d = src.get(x - 1, y);
+            let e = src.get(x, y);
+            let f = src.get(x + 1, y);
+            let g = src.get(x - 1, y + 1);
+            let h = src.get(x, y + 1);
+            let i = src.get(x + 1, y + 1);
+            let mut block = [e; 9];
+            if b != h && d != f {
+                if d == b { block[0] = d; }
+                if (d == b && e != c) || (b == f && e != a) { block[1] = b; }
+                if b == f { block[2] = f; }
+                if (d == b && e != g) || (d == h && e != a) { block[3] = d; }
+                if (b == f && e != i) || (h == f && e != c) { block[5] = f; }
+                if d == h { block[6] = d; }
+                if (d == h && e != i) || (h == f && e != g) { block[7] = h; }
+                if h == f { block[8] = f; }
+            }
+            for (n, color) in block.iter().enumerate() {
+                out.set(x as usize * 3 + n % 3, y as usize * 3 + n / 3, *color);
+            }
+        }
+    }
+    out.rgba
+}
+
+// hqx YUV thresholds, colors closer than this count as the same
+fn yuv_differs(a: [u8; 4], b: [u8; 4]) -> bool {
+    // the integer YUV of the original hq2x
+    let yuv = |c: [u8; 4]| {
+        let (r, g, b) = (c[0] as i32, c[1] as i32, c[2] as i32);
+        ((r + g + b) >> 2, 128 + ((r - b) >> 2), 128 + ((2 * g - r - b) >> 3))
+    };
+    let (y1, u1, v1) = yuv(a);
+    let (y2, u2, v2) = yuv(b);
+    (y1 - y2).abs() > 0x30 || (u1 - u2).abs() > 7 || (v1 - v2).abs() > 6
+}
+
+// neighbourhood orders that mirror each output pixel of hq2x onto the top left one
+const HQ2X_MIRRORS: [[usize; 9]; 4] = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8],
+    [2, 1, 0, 5, 4, 3, 8, 7, 6],
+    [6, 7, 8, 3, 4, 5, 0, 1, 2],
+    [8, 7, 6, 5, 4, 3, 2, 1, 0],
+];
+
+// hq2x. Each 3x3 neighbourhood is reduced to an 8 bit pattern of the neighbours that differ from
+// the centre, which picks the interpolation of every output pixel
+fn hq2x(src: &Image) -> Vec<u8> {
+    let mut out = Output::new(src, 2);
+    for y in 0..src.height as isize {
+        for x in 0..src.width as isize {
+            // w0 to w8 row by row, w4 is the pixel itself
+            let w: [[u8; 4]; 9] = std::array::from_fn(|n| src.get(x + n as isize % 3 - 1, y + n as isize / 3 - 1));
+            for (n, mirror) in HQ2X_MIRRORS.iter().enumerate() {
+                let color = hq2x_top_left(&mirror.map(|i| w[i]));
+                out.set(x as usize * 2 + n % 2, y as usize * 2 + n / 2, color);
+            }
+        }
+    }
+    out.rgba
+}
+
+// Top left output pixel of hq2x. These are the 256 cases of the original lookup table grouped by
+// which pattern bits they depend on, as in ffmpeg's hqx
+fn hq2x_top_left(w: &[[u8; 4]; 9]) -> [u8; 4] {
+    // bits 0 to 7 are w0, w1, w2, w3, w5, w6, w7, w8
+    let k = [0, 1, 2, 3, 5, 6, 7, 8]
+        .iter()
+        .enumerate()
+        .fold(0u8, |k, (bit, i)| k | (yuv_differs(w[4], w[*i]) as u8) << bit);
+    let any = |patterns: &[(u8, u8)]| patterns.iter().any(|(mask, value)| k & mask == *value);
+    let (w0, w1, w3, w4, w5, w7) = (w[0], w[1], w[3], w[4], w[5], w[7]);
+
+    if any(&[(0xbf, 0x37), (0xdb, 0x13)]) && yuv_differs(w1, w5) {
+        mix(&[(w4, 3), (w3, 1)])
+    } else if any(&[(0xdb, 0x49), (0xef, 0x6d)]) && yuv_differs(w7, w3) {
+        mix(&[(w4, 3), (w1, 1)])
+    } else if any(&[(0x0b, 0x0b), (0xfe, 0x4a), (0xfe, 0x1a)]) && yuv_differs(w3, w1) {
+        w4
+    } else if any(&[
+        (0x6f, 0x2a), (0x5b, 0x0a), (0xbf, 0x3a), (0xdf, 0x5a), (0x9f, 0x8a), (0xcf, 0x8a), (0xef, 0x4e),
+        (0x3f, 0x0e), (0xfb, 0x5a), (0xbb, 0x8a), (0x7f, 0x5a), (0xaf, 0x8a), (0xeb, 0x8a),
+    ]) && yuv_differs(w3, w1)
+    {
+        mix(&[(w4, 3), (w0, 1)])
+    } else if any(&[(0x0b, 0x08)]) {
+        mix(&[(w4, 2), (w0, 1), (w1, 1)])
+    } else if any(&[(0x0b, 0x02)]) {
+        mix(&[(w4, 2), (w0, 1), (w3, 1)])
+    } else if any(&[(0x2f, 0x2f)]) {
+        mix(&[(w4, 14), (w3, 1), (w1, 1)])
+    } else if any(&[(0xbf, 0x37), (0xdb, 0x13)]) {
+        mix(&[(w4, 5), (w1, 2), (w3, 1)])
+    } else if any(&[(0xdb, 0x49), (0xef, 0x6d)]) {
+        mix(&[(w4, 5), (w3, 2), (w1, 1)])
+    } else if any(&[(0x1b, 0x03), (0x4f, 0x43), (0x8b, 0x83), (0x6b, 0x43)]) {
+        mix(&[(w4, 3), (w3, 1)])
+    } else if any(&[(0x4b, 0x09), (0x8b, 0x89), (0x1f, 0x19), (0x3b, 0x19)]) {
+        mix(&[(w4, 3), (w1, 1)])
+    } else if any(&[(0x7e, 0x2a), (0xef, 0xab), (0xbf, 0x8f), (0x7e, 0x0e)]) {
+        mix(&[(w4, 2), (w3, 3), (w1, 3)])
+    } else if any(&[(0xfb, 0x6a), (0x6f, 0x6e), (0x3f, 0x3e), (0xfb, 0xfa), (0xdf, 0xde), (0xdf, 0x1e)]) {
+        mix(&[(w4, 3), (w0, 1)])
+    } else if any(&[
+        (0x0a, 0x00), (0x4f, 0x4b), (0x9f, 0x1b), (0x2f, 0x0b), (0xbe, 0x0a), (0xee, 0x0a), (0x7e, 0x0a),
+        (0xeb, 0x4b), (0x3b, 0x1b),
+    ]) {
+        mix(&[(w4, 2), (w3, 1), (w1, 1)])
+    } else {
+        mix(&[(w4, 6), (w3, 1), (w1, 1)])
+    }
+}
+
+// xBRZ blend strength of a pixel corner
+const BLEND_NONE: u8 = 0;
+const BLEND_NORMAL: u8 = 1;
+const BLEND_DOMINANT: u8 = 2;
+// xBRZ tuning, the reference defaults
+const XBRZ_EQUAL_TOLERANCE: f64 = 30.0;
+const XBRZ_CENTER_BIAS: f64 = 4.0;
+const XBRZ_DOMINANT_THRESHOLD: f64 = 3.6;
+const XBRZ_STEEP_THRESHOLD: f64 = 2.2;
+
+// YCbCr distance with BT.2020 weights, as used by xBRZ
+fn xbrz_dist(a: [u8; 4], b: [u8; 4]) -> f64 {
+    let (r, g, bl) = (a[0] as f64 - b[0] as f64, a[1] as f64 - b[1] as f64, a[2] as f64 - b[2] as f64);
+    let (k_b, k_r) = (0.0593, 0.2627);
+    let y = k_r * r + (1.0 - k_b - k_r) * g + k_b * bl;
+    let c_b = 0.5 / (1.0 - k_b) * (bl - y);
+    let c_r = 0.5 / (1.0 - k_r) * (r - y);
+    (y * y + c_b * c_b + c_r * c_r).sqrt()
+}
+
+// Blend strength of the four corners meeting in the middle of the 2x2 block with (x, y) at its
+// top left, in the order top left pixel (f), top right (g), bottom left (j), bottom right (k)
+fn xbrz_block_corners(src: &Image, x: isize, y: isize) -> [u8; 4] {
+    let p = |dx: isize, dy: isize| src.get(x + dx, y + dy);
+    let (b, c) = (p(0, -1), p(1, -1));
+    let (e, f, g, h) = (p(-1, 0), p(0, 0), p(1, 0), p(2, 0));
+    let (i, j, k, l) = (p(-1, 1), p(0, 1), p(1, 1), p(2, 1));
+    let (n, o) = (p(0, 2), p(1, 2));
+    let mut corners = [BLEND_NONE; 4];
+    if (f == g && j == k) || (f == j && g == k) {
+        return corners;
+    }
+    // edge strength along each diagonal of the block
+    let jg = xbrz_dist(i, f) + xbrz_dist(f, c) + xbrz_dist(n, k) + xbrz_dist(k, h) + XBRZ_CENTER_BIAS * xbrz_dist(j, g);
+    let fk = xbrz_dist(e, j) + xbrz_dist(j, o) + xbrz_dist(b, g) + xbrz_dist(g, l) + XBRZ_CENTER_BIAS * xbrz_dist(f, k);
+    if jg < fk {
+        let blend = if XBRZ_DOMINANT_THRESHOLD * jg < fk { BLEND_DOMINANT } else { BLEND_NORMAL };
+        if f != g && f != j {
+            corners[0] = blend;
+        }
+        if k != j && k != g {
+            corners[3] = blend;
+        }
+    } else if fk < jg {
+        let blend = if XBRZ_DOMINANT_THRESHOLD * fk < jg { BLEND_DOMINANT } else { BLEND_NORMAL };
+        if j != f && j != k {
+            corners[2] = blend;
+        }
+        if g != f && g != k {
+            corners[1] = blend;
+        }
+    }
+    corners
+}
+
+// xBRZ 2x. The corner blends of every 2x2 block are found first, then each pixel blends
+// its corners, handled one at a time by rotating the neighbourhood so the corner is bottom right
+fn xbrz2x(src: &Image) -> Vec<u8> {
+    // blocks start one pixel up and left so every pixel is inside four of them
+    let blocks_width = src.width + 1;
+    let mut blocks = Vec::with_capacity(blocks_width * (src.height + 1));
+    for y in -1..src.height as isize {
+        for x in -1..src.width as isize {
+            blocks.push(xbrz_block_corners(src, x, y));
+        }
+    }
+    let block = |x: usize, y: usize| blocks[y * blocks_width + x];
+
+    let mut out = Output::new(src, 2);
+    for y in 0..src.height {
+        for x in 0..src.width {
+            // blend info, two bits each for top left, top right, bottom right and bottom left
+            let info = block(x, y)[3]
+                | block(x + 1, y)[2] << 2
+                | block(x + 1, y + 1)[0] << 4
+                | block(x, y + 1)[1] << 6;
+            let e = src.get(x as isize, y as isize);
+            // output pixels top left, top right, bottom left, bottom right
+            let mut pixels = [e; 4];
+            if info != 0 {
+                for rotation in 0..4 {
+                    xbrz_blend_corner(src, x as isize, y as isize, rotation, info, &mut pixels);
+                }
+            }
+            for (n, color) in pixels.iter().enumerate() {
+                out.set(x * 2 + n % 2, y * 2 + n / 2, *color);
+            }
+        }
+    }
+    out.rgba
+}
+
+// Blends the bottom right corner of the pixel after rotating everything clockwise by rotation quarter turns
+fn xbrz_blend_corner(src: &Image, x: isize, y: isize, rotation: u32, info: u8, pixels: &mut [[u8; 4]; 4]) {
+    let info = info.rotate_left(rotation * 2);
+    let corner = |n: u32| (info >> (n * 2)) & 0b11;
+    // corners after rotating: 0 top left, 1 top right, 2 bottom right, 3 bottom left
+    let blend = corner(2);
+    if blend == BLEND_NONE {
+        return;
+    }
+
+    // unrotates a neighbourhood offset
+    let turn = |mut dx: isize, mut dy: isize| {
+        for _ in 0..rotation {
+            (dx, dy) = (dy, -dx);
+        }
+        (dx, dy)
+    };
+    let p = |dx: isize, dy: isize| {
+        let (dx, dy) = turn(dx, dy);
+        src.get(x + dx, y + dy)
+    };
+    let (b, c) = (p(0, -1), p(1, -1));
+    let (d, e, f) = (p(-1, 0), p(0, 0), p(1, 0));
+    let (g, h, i) = (p(-1, 1), p(0, 1), p(1, 1));
+    let eq = |a: [u8; 4], b: [u8; 4]| xbrz_dist(a, b) < XBRZ_EQUAL_TOLERANCE;
+
+    let line_blend = if blend >= BLEND_DOMINANT {
+        true
+    } else if corner(1) != BLEND_NONE && !eq(e, g) {
+        // another corner of this pixel blends, keeps single pixels like eyes intact
+        false
+    } else if corner(3) != BLEND_NONE && !eq(e, c) {
+        false
+    } else {
+        // no full blend for L shapes, only the corner
+        !(!eq(e, i) && eq(g, h) && eq(h, i) && eq(i, f) && eq(f, c))
+    };
+    let color = if xbrz_dist(e, f) <= xbrz_dist(e, h) { f } else { h };
+
+    // blends color into an output pixel given in rotated (column, row), weight of color / total
+    let mut blend_into = |col: usize, row: usize, weight: u32, total: u32| {
+        let (mut col, mut row) = (col, row);
+        for _ in 0..rotation {
+            (col, row) = (row, 1 - col);
+        }
+        let pixel = &mut pixels[row * 2 + col];
+        *pixel = mix(&[(color, weight), (*pixel, total - weight)]);
+    };
+    if line_blend {
+        let fg = xbrz_dist(f, g);
+        let hc = xbrz_dist(h, c);
+        let shallow = XBRZ_STEEP_THRESHOLD * fg <= hc && e != g && d != g;
+        let steep = XBRZ_STEEP_THRESHOLD * hc <= fg && e != c && b != c;
+        match (shallow, steep) {
+            (true, true) => {
+                blend_into(0, 1, 1, 4);
+                blend_into(1, 0, 1, 4);
+                blend_into(1, 1, 5, 6);
+            }
+            (true, false) => {
+                blend_into(0, 1, 1, 4);
+                blend_into(1, 1, 3, 4);
+            }
+            (false, true) => {
+                blend_into(1, 0, 1, 4);
+                blend_into(1, 1, 3, 4);
+            }
+            (false, false) => blend_into(1, 1, 1, 2),
+        }
+    } else {
+        // area of the corner cut off by a circle, 1 - pi / 4
+        blend_into(1, 1, 211, 1000);
+    }
+}
+
+// 3x3 dots with the right column and bottom row darkened as the gap between LCD pixels
+fn lcd_grid(src: &Image) -> Vec<u8> {
+    let mut out = Output::new(src, 3);
+    for y in 0..src.height as isize {
+        for x in 0..src.width as isize {
+            let e = src.get(x, y);
+            let gap = mix(&[(e, 3), ([0, 0, 0, e[3]], 1)]);
+            for n in 0..9 {
+                let color = if n % 3 == 2 || n / 3 == 2 { gap } else { e };
+                out.set(x as usize * 3 + n % 3, y as usize * 3 + n / 3, color);
+            }
+        }
+    }
+    out.rgba
+}
@@ -0,0 +1,194 @@
+// GBS (Game Boy Sound System) music rips, the sound driver of a game with a small header.
+// The rip is placed at its load address behind a tiny driver that calls init once, then play
+// on every vblank or timer interrupt
+use std::fs;
+use std::path::Path;
+
+const HEADER_SIZE: usize = 0x70;
+// lowest load address, the space below holds the rst and interrupt vectors and the driver
+const MIN_LOAD_ADDRESS: u16 = 0x400;
+// emulated time of one frame
+const FRAME_SECS: f32 = 70224.0 / 4194304.0;
+
+#[derive(Clone, Debug)]
+pub struct GbsHeader {
+    pub song_count: u8,
+    // 1 based
+    pub first_song: u8,
+    pub load_address: u16,
+    pub init_address: u16,
+    pub play_address: u16,
+    pub stack_pointer: u16,
+    pub timer_modulo: u8,
+    pub timer_control: u8,
+    pub title: String,
+    pub author: String,
+    pub copyright: String,
+}
+
+impl GbsHeader {
+    pub fn parse(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.len() < HEADER_SIZE || &bytes[0..3] != b"GBS" {
+            return Err(String::from("Not a GBS file"));
+        }
+        if bytes[3] != 1 {
+            return Err(format!("Unsupported GBS version {}", bytes[3]));
+        }
+        let word = |offset: usize| u16::from_le_bytes([bytes[offset], bytes[offset + 1]]);
+        // fixed 32 byte fields padded with zeros
+        let text = |offset: usize| {
+            let field = &bytes[offset..offset + 32];
+            let end = field.iter().position(|b| *b == 0).unwrap_or(field.len());
+            field[..end].iter().map(|b| *b as char).collect::<String>()
+        };
+        let header = Self {
+            song_count: bytes[4],
+            first_song: bytes[5],
+            load_address: word(6),
+            init_address: word(8),
+            play_address: word(0x0a),
+            stack_pointer: word(0x0c),
+            timer_modulo: bytes[0x0e],
+            timer_control: bytes[0x0f],
+            title: text(0x10),
+            author: text(0x30),
+            copyright: text(0x50),
+        };
+        if header.song_count == 0 {
+            return Err(String::from("GBS file has no songs"));
+        }
+        if header.load_address < MIN_LOAD_ADDRESS {
+            return Err(format!("Unsupported GBS load address {:04X}", header.load_address));
+        }
+        Ok(header)
+    }
+
+    // true when play is called from the timer interrupt instead of vblank
+    pub fn uses_timer(&self) -> bool {
+        self.timer_control & 0b100 != 0
+    }
+}
+
+pub struct GbsPlayer {
+    pub header: GbsHeader,
+    data: Vec<u8>,
+    // 0 based
+    track: usize,
+    frames: u32,
+    // frame the fade out started, None while playing normally
+    fade_start: Option<u32>,
+    // seconds a track plays before fading out on its own, 0 plays forever
+    pub track_length: f32,
+    pub fade_length: f32,
+}
+
+impl GbsPlayer {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let bytes = fs::read(path).map_err(|e| e.to_string())?;
+        let header = GbsHeader::parse(&bytes)?;
+        Ok(Self {
+            track: header.first_song.saturating_sub(1) as usize,
+            header,
+            data: bytes[HEADER_SIZE..].to_vec(),
+            frames: 0,
+            fade_start: None,
+            track_length: 150.0,
+            fade_length: 8.0,
+        })
+    }
+
+    pub fn track(&self) -> usize {
+        self.track
+    }
+    pub fn track_count(&self) -> usize {
+        self.header.song_count as usize
+    }
+
+    // Restarts the timing for a track and builds the rom image that plays it
+    pub fn start(&mut self, track: usize) -> Vec<u8> {
+        self.track = track.min(self.track_count() - 1);
+        self.frames = 0;
+        self.fade_start = None;
+        self.image()
+    }
+
+    // Seconds of emulated time the track has played
+    pub fn elapsed(&self) -> f32 {
+        self.frames as f32 * FRAME_SECS
+    }
+
+    pub fn fade_out(&mut self) {
+        if self.fade_start.is_none() {
+            self.fade_start = Some(self.frames);
+        }
+    }
+
+    // Counts a finished frame, returns the volume of the fade out
+    pub fn advance_frame(&mut self) -> f32 {
+        if !self.finished() {
+            self.frames += 1;
+        }
+        if self.track_length > 0.0 && self.elapsed() >= self.track_length {
+            self.fade_out();
+        }
+        self.gain()
+    }
+
+    pub fn gain(&self) -> f32 {
+        match self.fade_start {
+            Some(start) if self.fade_length > 0.0 => {
+                let fading = (self.frames - start) as f32 * FRAME_SECS;
+                (1.0 - fading / self.fade_length).clamp(0.0, 1.0)
+            }
+            Some(_) => 0.0,
+            None => 1.0,
+        }
+    }
+
+    // true once the fade out is complete
+    pub fn finished(&self) -> bool {
+        self.fade_start.is_some() && self.gain() == 0.0
+    }
+
+    // Rom image with the rip at its load address, vectors pointing into it and the driver at 0100
+    fn image(&self) -> Vec<u8> {
+        let header = &self.header;
+        let load = header.load_address as usize;
+        let size = (load + self.data.len()).div_ceil(0x4000).max(2) * 0x4000;
+        let mut rom = vec![0; size];
+        rom[load..load + self.data.len()].copy_from_slice(&self.data);
+
+        // rst vectors are relocated to the load address
+        for vector in (0..0x40).step_by(8) {
+            let [low, high] = (header.load_address + vector as u16).to_le_bytes();
+            rom[vector..vector + 3].copy_from_slice(&[0xc3, low, high]);
+        }
+
+        // the interrupt calls play
+        let (vector, enable) = if header.uses_timer() { (0x50, 0b100) } else { (0x40, 0b1) };
+        let [play_low, play_high] = header.play_address.to_le_bytes();
+        rom[vector..vector + 4].copy_from_slice(&[0xcd, play_low, play_high, 0xd9]);
+
+        let [sp_low, sp_high] = header.stack_pointer.to_le_bytes();
+        let [init_low, init_high] = header.init_address.to_le_bytes();
+        let driver = [
+            0xf3, // di
+            0x31, sp_low, sp_high, // ld sp, stack pointer
+            0x3e, header.timer_modulo, // ld a, tma
+            0xe0, 0x06, // ldh (tma), a
+            0x3e, header.timer_control & 0b111, // ld a, tac
+            0xe0, 0x07, // ldh (tac), a
+            0x3e, self.track as u8, // ld a, track
+            0xcd, init_low, init_high, // call init
+            0xaf, // xor a
+            0xe0, 0x0f, // ldh (if), a
+            0x3e, enable, // ld a, interrupt
+            0xe0, 0xff, // ldh (ie), a
+            0xfb, // ei
+            0x76, // halt
+            0x18, 0xfd, // jr halt
+        ];
+        rom[0x100..0x100 + driver.len()].copy_from_slice(&driver);
+        rom
+    }
+}
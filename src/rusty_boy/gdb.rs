@@ -0,0 +1,323 @@
+// GDB remote serial protocol server, lets external debuggers drive the emulator over local TCP
+use crate::rusty_boy::cpu::CPU;
+use crate::rusty_boy::debugger::Debugger;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+// register order in g/G packets, each 16 bits little endian
+const REGISTERS: [&str; 6] = ["af", "bc", "de", "hl", "sp", "pc"];
+// largest packet we accept and send, advertised in qSupported
+const PACKET_SIZE: usize = 0x1000;
+// a client that leaves this much output unread is dropped
+const MAX_PENDING: usize = 0x10000;
+
+pub struct GdbServer {
+    listener: TcpListener,
+    client: Option<TcpStream>,
+    // received bytes not yet parsed into packets
+    buffer: Vec<u8>,
+    // replies the socket didn't take yet, flushed on later polls
+    output: Vec<u8>,
+    // a continue or step is running, a stop reply is due once the debugger pauses
+    running: bool,
+}
+
+impl GdbServer {
+    // Listens on localhost only
+    pub fn bind(port: u16) -> Result<Self, String> {
+        let listener = TcpListener::bind(("127.0.0.1", port)).map_err(|e| e.to_string())?;
+        listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+        Ok(Self {
+            listener,
+            client: None,
+            buffer: Vec::new(),
+            output: Vec::new(),
+            running: false,
+        })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.listener.local_addr().map(|addr| addr.port()).unwrap_or(0)
+    }
+    pub fn connected(&self) -> bool {
+        self.client.is_some()
+    }
+
+    // Accepts a client and answers its packets, called once per frame before the cpu runs
+    pub fn poll(&mut self, cpu: &mut CPU, debugger: &mut Debugger) {
+        if self.client.is_none() {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    if stream.set_nonblocking(true).is_err() {
+                        return;
+                    }
+                    let _ = stream.set_nodelay(true);
+                    self.client = Some(stream);
+                    self.buffer.clear();
+                    self.output.clear();
+                    self.running = false;
+                    // gdb expects the target to be stopped when it attaches
+                    debugger.pause(String::from("GDB attached"));
+                }
+                Err(_) => return,
+            }
+        }
+
+        if !self.receive() {
+            self.disconnect(debugger);
+            return;
+        }
+
+        while let Some(packet) = self.next_packet() {
+            match packet {
+                Packet::Interrupt => {
+                    debugger.pause(String::from("Interrupted by GDB"));
+                }
+                Packet::Data(data) => {
+                    self.send_raw(b"+");
+                    match self.handle(&data, cpu, debugger) {
+                        Reply::Send(reply) => self.send(&reply),
+                        Reply::Wait => {}
+                        Reply::Detach => {
+                            self.send("OK");
+                            self.flush();
+                            self.disconnect(debugger);
+                            return;
+                        }
+                    }
+                }
+                Packet::Corrupt => self.send_raw(b"-"),
+            }
+        }
+
+        // report the stop once the continue or step finished
+        if self.running && debugger.paused() {
+            self.running = false;
+            self.send("S05");
+        }
+
+        if !self.flush() {
+            self.disconnect(debugger);
+        }
+    }
+
+    // Reads everything available, false when the client went away
+    fn receive(&mut self) -> bool {
+        let Some(client) = &mut self.client else {
+            return false;
+        };
+        let mut chunk = [0u8; 1024];
+        loop {
+            match client.read(&mut chunk) {
+                Ok(0) => return false,
+                Ok(n) => self.buffer.extend_from_slice(&chunk[..n]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return true,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => return false,
+            }
+        }
+    }
+
+    fn next_packet(&mut self) -> Option<Packet> {
+        // acks from the client need no handling, anything else between packets is garbage
+        let start = self.buffer.iter().position(|b| *b == b'$' || *b == 0x03).unwrap_or(self.buffer.len());
+        self.buffer.drain(..start);
+        match self.buffer.first()? {
+            0x03 => {
+                self.buffer.drain(..1);
+                Some(Packet::Interrupt)
+            }
+            _ => {
+                let end = self.buffer.iter().position(|b| *b == b'#')?;
+                if self.buffer.len() < end + 3 {
+                    return None;
+                }
+                let data = self.buffer[1..end].to_vec();
+                let checksum = std::str::from_utf8(&self.buffer[end + 1..end + 3])
+                    .ok()
+                    .and_then(|text| u8::from_str_radix(text, 16).ok());
+                self.buffer.drain(..end + 3);
+                if checksum == Some(checksum_of(&data)) {
+                    Some(Packet::Data(String::from_utf8_lossy(&data).into_owned()))
+                } else {
+                    Some(Packet::Corrupt)
+                }
+            }
+        }
+    }
+
+    // Answers a packet, register and memory access go straight to the cpu
+    fn handle(&mut self, packet: &str, cpu: &mut CPU, debugger: &mut Debugger) -> Reply {
+        let (command, args) = packet.split_at(packet.len().min(1));
+        let reply = match command {
+            "?" => String::from("S05"),
+            "g" => REGISTERS
+                .iter()
+                .map(|reg| {
+                    let value = cpu.registers.get_u16_reg(reg).unwrap();
+                    hex_bytes(&value.to_le_bytes())
+                })
+                .collect(),
+            "G" => {
+                let bytes = parse_hex_bytes(args);
+                if bytes.len() < REGISTERS.len() * 2 {
+                    return Reply::Send(String::from("E01"));
+                }
+                for (n, reg) in REGISTERS.iter().enumerate() {
+                    let value = u16::from_le_bytes([bytes[n * 2], bytes[n * 2 + 1]]);
+                    cpu.registers.set_u16_reg(reg, value).unwrap();
+                }
+                String::from("OK")
+            }
+            "p" => match usize::from_str_radix(args, 16).ok().and_then(|n| REGISTERS.get(n)) {
+                Some(reg) => hex_bytes(&cpu.registers.get_u16_reg(reg).unwrap().to_le_bytes()),
+                None => String::from("E01"),
+            },
+            "P" => {
+                let Some((index, value)) = args.split_once('=') else {
+                    return Reply::Send(String::from("E01"));
+                };
+                let bytes = parse_hex_bytes(value);
+                match (usize::from_str_radix(index, 16).ok().and_then(|n| REGISTERS.get(n)), bytes.len()) {
+                    (Some(reg), 2) => {
+                        cpu.registers.set_u16_reg(reg, u16::from_le_bytes([bytes[0], bytes[1]])).unwrap();
+                        String::from("OK")
+                    }
+                    _ => String::from("E01"),
+                }
+            }
+            "m" => match parse_address_length(args) {
+                // two hex digits per byte, gdb asks again for the rest of a short read
+                Some((address, length)) => (0..length.min(PACKET_SIZE / 2).min(0x10000 - address as usize))
+                    .map(|i| format!("{:02x}", cpu.memory.peek(address.wrapping_add(i as u16))))
+                    .collect(),
+                None => String::from("E01"),
+            },
+            "M" => {
+                let Some((range, data)) = args.split_once(':') else {
+                    return Reply::Send(String::from("E01"));
+                };
+                match parse_address_length(range) {
+                    Some((address, _)) => {
+                        for (i, byte) in parse_hex_bytes(data).into_iter().enumerate() {
+                            cpu.memory.poke(address.wrapping_add(i as u16), byte);
+                        }
+                        String::from("OK")
+                    }
+                    None => String::from("E01"),
+                }
+            }
+            "c" | "s" => {
+                // optional resume address
+                if let Ok(address) = u16::from_str_radix(args, 16) {
+                    cpu.registers.pc = address;
+                }
+                if command == "c" {
+                    debugger.resume();
+                } else {
+                    debugger.step_into();
+                }
+                self.running = true;
+                // the stop reply is sent once the debugger pauses again
+                return Reply::Wait;
+            }
+            "Z" | "z" => {
+                // software and hardware execution breakpoints, watchpoints are not supported
+                let mut parts = args.split(',');
+                let kind = parts.next();
+                let address = parts.next().and_then(|a| u16::from_str_radix(a, 16).ok());
+                match (kind, address) {
+                    (Some("0") | Some("1"), Some(address)) => {
+                        let exists = debugger.breakpoints.iter().any(|b| b.address == address && b.bank.is_none() && b.condition.is_none());
+                        if (command == "Z") != exists {
+                            debugger.toggle_breakpoint(address, None);
+                        }
+                        String::from("OK")
+                    }
+                    _ => String::new(),
+                }
+            }
+            "H" => String::from("OK"),
+            "q" if args == "Attached" => String::from("1"),
+            "q" if args.starts_with("Supported") => format!("PacketSize={:x}", PACKET_SIZE),
+            "q" if args == "C" => String::from("QC1"),
+            "D" | "k" => return Reply::Detach,
+            _ => String::new(),
+        };
+        Reply::Send(reply)
+    }
+
+    fn send(&mut self, data: &str) {
+        let packet = format!("${}#{:02x}", data, checksum_of(data.as_bytes()));
+        self.send_raw(packet.as_bytes());
+    }
+    fn send_raw(&mut self, bytes: &[u8]) {
+        if self.client.is_some() {
+            self.output.extend_from_slice(bytes);
+        }
+    }
+    // Writes as much pending output as the socket takes, false when the client is gone or not reading
+    fn flush(&mut self) -> bool {
+        let Some(client) = &mut self.client else {
+            return true;
+        };
+        let mut written = 0;
+        while written < self.output.len() {
+            match client.write(&self.output[written..]) {
+                Ok(0) => return false,
+                Ok(n) => written += n,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => return false,
+            }
+        }
+        self.output.drain(..written);
+        self.output.len() <= MAX_PENDING
+    }
+
+    fn disconnect(&mut self, debugger: &mut Debugger) {
+        self.client = None;
+        self.buffer.clear();
+        self.output.clear();
+        self.running = false;
+        debugger.resume();
+    }
+}
+
+// Answer to a packet
+enum Reply {
+    // empty for unsupported packets
+    Send(String),
+    // continue or step, answered with a stop reply later
+    Wait,
+    // detach or kill, ends the session
+    Detach,
+}
+
+enum Packet {
+    Data(String),
+    // ctrl-c from the client
+    Interrupt,
+    Corrupt,
+}
+
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, b| sum.wrapping_add(*b))
+}
+
+fn hex_bytes(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn parse_hex_bytes(text: &str) -> Vec<u8> {
+    text.as_bytes()
+        .chunks(2)
+        .filter_map(|pair| std::str::from_utf8(pair).ok().and_then(|p| u8::from_str_radix(p, 16).ok()))
+        .collect()
+}
+
+// "addr,length" as used by m and M
+fn parse_address_length(text: &str) -> Option<(u16, usize)> {
+    let (address, length) = text.split_once(',')?;
+    Some((u16::from_str_radix(address, 16).ok()?, usize::from_str_radix(length, 16).ok()?))
+}
@@ -1,8 +1,50 @@
+use std::cell::Cell;
 use std::rc::{Rc};
 use multi_compare::c;
 use crate::rusty_boy::cartridge::Cartridge;
+use crate::rusty_boy::debugger::{WatchHit, WatchKind, Watchpoint};
 use crate::rusty_boy::motherboard::Motherboard;
 
+// Address space shown by the hex editor
+#[derive(PartialEq, Clone, Copy, Debug, Eq)]
+pub enum MemoryView {
+    // full 64 KiB as the cpu sees it
+    Bus,
+    // cartridge rom bank, 16 KiB each
+    Rom(usize),
+    // cartridge ram bank, 8 KiB each
+    Ram(usize),
+}
+
+impl MemoryView {
+    // cpu address of the first byte in the view
+    pub fn base(&self) -> u16 {
+        match self {
+            MemoryView::Bus => 0,
+            MemoryView::Rom(0) => 0,
+            MemoryView::Rom(_) => 0x4000,
+            MemoryView::Ram(_) => 0xa000,
+        }
+    }
+}
+
+// Name of the memory region an address belongs to
+pub fn region_name(address: u16) -> &'static str {
+    match address {
+        0x0000..=0x3fff => "ROM0",
+        0x4000..=0x7fff => "ROMX",
+        0x8000..=0x9fff => "VRAM",
+        0xa000..=0xbfff => "SRAM",
+        0xc000..=0xdfff => "WRAM",
+        0xe000..=0xfdff => "ECHO",
+        0xfe00..=0xfe9f => "OAM",
+        0xfea0..=0xfeff => "----",
+        0xff00..=0xff7f => "IO",
+        0xff80..=0xfffe => "HRAM",
+        0xffff => "IE",
+    }
+}
+
 pub struct Memory {
     pub cartridge: Cartridge,
     // internal high ram
@@ -12,28 +54,120 @@ pub struct Memory {
     // serial bus
     sb1: u8,
     sb2: u8,
+    // accuracy option, blocks vram in mode 3 and oam in modes 2 and 3
+    pub access_restrictions: bool,
+    // debugger watchpoints, only checked when not empty
+    pub watchpoints: Vec<Watchpoint>,
+    // last watchpoint hit, taken by the cpu after each instruction
+    pub watch_hit: Cell<Option<WatchHit>>,
     // motherboard pointer
     motherboard: Rc<Motherboard>
 }
 
 impl Memory {
-    pub fn new(rom: Vec<u8>, mobo: &Rc<Motherboard>) -> Self {
+    pub fn new(cartridge: Cartridge, mobo: &Rc<Motherboard>) -> Self {
         Self {
-            cartridge: Cartridge::new(rom).unwrap(),
+            cartridge,
             motherboard: mobo.clone(),
             h_ram: [0; 128],
             i_ram: [0; 0x2000],
             sb1: 0,
             sb2: 0,
+            access_restrictions: false,
+            watchpoints: Vec::new(),
+            watch_hit: Cell::new(None),
         }
     }
     pub fn get(&self, address: u16) -> u8 {
+        let value = self.read(address);
+        if !self.watchpoints.is_empty() {
+            self.watch(address, WatchKind::Read, value, value);
+        }
+        value
+    }
+    pub fn set(&mut self, address: u16, value: u8) {
+        if self.watchpoints.is_empty() {
+            self.write(address, value);
+            return;
+        }
+        // change detection must not sync the hardware or trip read watchpoints
+        let old = self.peek(address);
+        self.write(address, value);
+        self.watch(address, WatchKind::Write, old, value);
+        let new = self.peek(address);
+        if new != old {
+            self.watch(address, WatchKind::Change, old, new);
+        }
+    }
+    // Reads without watchpoints, hardware sync or the ppu lock, for the debug views and dma
+    pub fn peek(&self, address: u16) -> u8 {
+        self.load(address, true)
+    }
+    // Writes without triggering watchpoints or the ppu lock, for debugger edits. Mbc and io writes
+    // still behave as the game would see them
+    pub fn poke(&mut self, address: u16, value: u8) {
+        self.store(address, value, true);
+    }
+    // Cartridge bank mapped at an address, 0 outside the cartridge areas
+    pub fn bank(&self, address: u16) -> u16 {
+        if address < 0x8000 || c!(0xa000 <= address < 0xc000) {
+            self.cartridge.bank(address)
+        } else {
+            0
+        }
+    }
+    // Reads a whole view for the hex editor, without triggering watchpoints
+    pub fn read_view(&self, view: MemoryView) -> Vec<u8> {
+        match view {
+            MemoryView::Bus => (0..=0xffff).map(|address| self.peek(address)).collect(),
+            MemoryView::Rom(bank) => bank_slice(&self.cartridge.rom, bank, 0x4000).to_vec(),
+            MemoryView::Ram(bank) => bank_slice(&self.cartridge.ram, bank, 0x2000).to_vec(),
+        }
+    }
+    // Bus edits go through poke, bank views patch the cartridge data directly
+    pub fn write_view(&mut self, view: MemoryView, offset: usize, value: u8) {
+        match view {
+            MemoryView::Bus => self.poke(offset as u16, value),
+            MemoryView::Rom(bank) => {
+                if let Some(byte) = self.cartridge.rom.get_mut(bank * 0x4000 + offset) {
+                    *byte = value;
+                }
+            }
+            MemoryView::Ram(bank) => {
+                if let Some(byte) = self.cartridge.ram.get_mut(bank * 0x2000 + offset) {
+                    *byte = value;
+                }
+            }
+        }
+    }
+    // Records a hit when an enabled watchpoint of the kind covers the address
+    fn watch(&self, address: u16, kind: WatchKind, old: u8, new: u8) {
+        let hit = self.watchpoints.iter().any(|w| {
+            w.enabled && w.kind == kind && (w.start..=w.end).contains(&address)
+        });
+        if hit {
+            let bank = if address < 0x8000 || c!(0xa000 <= address < 0xc000) {
+                Some(self.cartridge.bank(address))
+            } else {
+                None
+            };
+            self.watch_hit.set(Some(WatchHit { address, kind, old, new, bank }));
+        }
+    }
+    fn read(&self, address: u16) -> u8 {
+        self.load(address, false)
+    }
+    // direct reads skip the hardware sync and the ppu lock, cpu reads catch the hardware up first
+    fn load(&self, address: u16, direct: bool) -> u8 {
         // cartridge rom read
         if address < 0x8000 {
             self.cartridge.read(address)
         }
         // vram
         else if c!(0x8000 <= address < 0xa000) {
+            if !direct && self.ppu_locked(address) {
+                return 0xff;
+            }
             self.motherboard.screen.borrow().get(address)
         }
         // cartridge ram
@@ -46,10 +180,13 @@ impl Memory {
         }
         // echo ram
         else if c!(0xe000 <= address < 0xfe00) {
-            self.get(address - 0x2000)
+            self.load(address - 0x2000, direct)
         }
         // oam
         else if c!(0xfe00 <= address < 0xfea0) {
+            if !direct && self.ppu_locked(address) {
+                return 0xff;
+            }
             self.motherboard.screen.borrow().get(address)
         }
         // prohibited region
@@ -58,7 +195,7 @@ impl Memory {
         }
         // I/O registers
         else if c!(0xff00 <= address < 0xff80) {
-            self.io_read(address)
+            self.io_read(address, !direct)
         }
         // hram 
         else if c!(0xff80 <= address <= 0xfffe) {
@@ -73,13 +210,20 @@ impl Memory {
             unreachable!()
         }
     }
-    pub fn set(&mut self, address: u16, value: u8) {
+    fn write(&mut self, address: u16, value: u8) {
+        self.store(address, value, false);
+    }
+    // direct writes reach vram and oam even while the ppu owns them
+    fn store(&mut self, address: u16, value: u8, direct: bool) {
         // cartridge rom read
         if address < 0x8000 {
             self.cartridge.write(address, value);
         }
         // vram
         else if c!(0x8000 <= address < 0xa000) {
+            if !direct && self.ppu_locked(address) {
+                return;
+            }
             self.motherboard.screen.borrow_mut().set(address, value);
         }
         // cartridge ram
@@ -92,10 +236,13 @@ impl Memory {
         }
         // echo ram
         else if c!(0xe000 <= address < 0xfe00) {
-            self.set(address - 0x2000, value);
+            self.store(address - 0x2000, value, direct);
         }
         // oam
         else if c!(0xfe00 <= address < 0xfea0) {
+            if !direct && self.ppu_locked(address) {
+                return;
+            }
             self.motherboard.screen.borrow_mut().set(address, value);
         }
         // prohibited region
@@ -120,7 +267,39 @@ impl Memory {
         }
     }
     
-    fn io_read(&self, address: u16) -> u8 {
+    // checks if the ppu currently owns the vram or oam bus
+    fn ppu_locked(&self, address: u16) -> bool {
+        if !self.access_restrictions {
+            return false;
+        }
+        // catch the screen up so the mode is current
+        self.motherboard.sync();
+        let screen = self.motherboard.screen.borrow();
+        if !screen.lcdc.lcd_enable {
+            return false;
+        }
+        match address {
+            // vram is locked during pixel transfer
+            0x8000..0xa000 => screen.stat.mode == 3,
+            // oam is locked during oam scan and pixel transfer
+            0xfe00..0xfea0 => screen.stat.mode == 2 || screen.stat.mode == 3,
+            _ => false
+        }
+    }
+
+    // DMG oam corruption bug, triggered by 16 bit inc/dec and push/pop on an oam address during mode 2
+    pub fn oam_bug(&self, address: u16, read: bool) {
+        if !self.access_restrictions || !c!(0xfe00 <= address <= 0xfeff) {
+            return;
+        }
+        self.motherboard.sync();
+        let mut screen = self.motherboard.screen.borrow_mut();
+        if screen.lcdc.lcd_enable && screen.stat.mode == 2 {
+            screen.corrupt_oam(read);
+        }
+    }
+
+    fn io_read(&self, address: u16, sync: bool) -> u8 {
         match address {
             // joypad
             0xff00 => self.motherboard.joypad.borrow().get_joypad(),
@@ -134,7 +313,9 @@ impl Memory {
             }
             // timer
             0xff04..=0xff07 => {
-                self.motherboard.sync();
+                if sync {
+                    self.motherboard.sync();
+                }
                 self.motherboard.timer.borrow().get(address)
             }
             // interrupt
@@ -143,17 +324,23 @@ impl Memory {
             }
             // audio
             0xff10..=0xff26 => {
-                self.motherboard.sync();
+                if sync {
+                    self.motherboard.sync();
+                }
                 self.motherboard.audio.borrow().get(address)
             },
             // audio ram
             0xff30..=0xff3f => {
-                self.motherboard.sync();
+                if sync {
+                    self.motherboard.sync();
+                }
                 self.motherboard.audio.borrow().get(address)
             },
             // screen
             0xff40..=0xff4b => {
-                self.motherboard.sync();
+                if sync {
+                    self.motherboard.sync();
+                }
                 self.motherboard.screen.borrow().get(address)
             }
             _ => 0xff
@@ -227,7 +414,15 @@ impl Memory {
     fn dma(&mut self, value: u8) {
         let offset: u16 = value as u16 * 0x100;
         for n in 0..0xa0 {
-            self.set(0xfe00 + n, self.get(n + offset));
+            // dma reads and writes bypass the cpu access restrictions and the watchpoints
+            let byte = self.peek(n + offset);
+            self.motherboard.screen.borrow_mut().oam[n as usize] = byte;
         }
     }
-}
\ No newline at end of file
+}
+
+// One bank of cartridge data, shorter when the data ends early
+fn bank_slice(data: &[u8], bank: usize, size: usize) -> &[u8] {
+    let start = (bank * size).min(data.len());
+    &data[start..(start + size).min(data.len())]
+}
@@ -34,6 +34,19 @@ impl Motherboard {
         }})
     }
     
+    // Powers the components back on for a new cartridge, the audio keeps its output device
+    pub fn reset(self: &Rc<Self>) {
+        self.i_flag.set(0);
+        self.i_enable.set(0);
+        self.cycles.set(0);
+        self.sync_cycles.set(0);
+        self.i_master.set(false);
+        *self.timer.borrow_mut() = Timer::new();
+        *self.screen.borrow_mut() = Screen::new(Rc::downgrade(self));
+        *self.joypad.borrow_mut() = Joypad::new();
+        self.audio.borrow_mut().reset();
+    }
+
     // sets interrupt flag
     pub fn set_interrupt(&self, bit: u8) {
         let flag = 1 << bit;
@@ -0,0 +1,204 @@
+// Minimal PNG encoder for RGBA8 frames, uses stored (uncompressed) deflate blocks
+// so it needs no compression library. The matching decoder reads these files back for
+// comparisons against reference images
+use std::fs;
+use std::path::Path;
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+// max bytes in a stored deflate block
+const STORED_BLOCK_SIZE: usize = 0xffff;
+
+// Encodes a RGBA8 buffer into PNG file bytes
+pub fn encode_png(width: usize, height: usize, rgba: &[u8]) -> Vec<u8> {
+    assert_eq!(rgba.len(), width * height * 4);
+
+    // IHDR: width, height, bit depth 8, color type 6 (RGBA), no interlace
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&(width as u32).to_be_bytes());
+    ihdr.extend_from_slice(&(height as u32).to_be_bytes());
+    ihdr.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+    // each scanline is prefixed with filter type 0 (none)
+    let mut raw = Vec::with_capacity((width * 4 + 1) * height);
+    for row in rgba.chunks(width * 4) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+
+    let mut png = PNG_SIGNATURE.to_vec();
+    write_chunk(&mut png, b"IHDR", &ihdr);
+    write_chunk(&mut png, b"IDAT", &zlib_stored(&raw));
+    write_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+// Decodes PNG file bytes written by encode_png, or any 8 bit RGBA PNG without compression.
+// Returns width, height and the RGBA8 buffer
+pub fn decode_png(png: &[u8]) -> Result<(usize, usize, Vec<u8>), String> {
+    if png.len() < 8 || png[..8] != PNG_SIGNATURE {
+        return Err(String::from("Not a PNG file"));
+    }
+    let mut header = None;
+    let mut idat = Vec::new();
+    let mut offset = 8;
+    while offset + 12 <= png.len() {
+        let len = u32::from_be_bytes(png[offset..offset + 4].try_into().unwrap()) as usize;
+        let kind = &png[offset + 4..offset + 8];
+        let data = png.get(offset + 8..offset + 8 + len).ok_or("Truncated PNG chunk")?;
+        match kind {
+            b"IHDR" if data.len() == 13 => header = Some(data.to_vec()),
+            b"IDAT" => idat.extend_from_slice(data),
+            b"IEND" => break,
+            _ => {}
+        }
+        offset += len + 12;
+    }
+    let header = header.ok_or("PNG has no header")?;
+    let width = u32::from_be_bytes(header[0..4].try_into().unwrap()) as usize;
+    let height = u32::from_be_bytes(header[4..8].try_into().unwrap()) as usize;
+    if header[8..13] != [8, 6, 0, 0, 0] {
+        return Err(String::from("Only 8 bit RGBA PNGs without interlacing are supported"));
+    }
+
+    let raw = zlib_unstored(&idat)?;
+    let stride = width * 4;
+    if raw.len() != (stride + 1) * height {
+        return Err(String::from("PNG image data has the wrong size"));
+    }
+    let mut rgba = vec![0; stride * height];
+    for y in 0..height {
+        let line = &raw[y * (stride + 1)..(y + 1) * (stride + 1)];
+        for x in 0..stride {
+            // left, up and up left bytes of the same channel, already unfiltered
+            let a = if x >= 4 { rgba[y * stride + x - 4] } else { 0 };
+            let b = if y > 0 { rgba[(y - 1) * stride + x] } else { 0 };
+            let c = if x >= 4 && y > 0 { rgba[(y - 1) * stride + x - 4] } else { 0 };
+            let predictor = match line[0] {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((a as u16 + b as u16) / 2) as u8,
+                4 => paeth(a, b, c),
+                filter => return Err(format!("Unknown PNG filter {filter}")),
+            };
+            rgba[y * stride + x] = line[x + 1].wrapping_add(predictor);
+        }
+    }
+    Ok((width, height, rgba))
+}
+
+// Compares a RGBA8 buffer with a reference PNG file, returns the number of differing pixels.
+// A size mismatch is an error
+pub fn compare_with_reference(width: usize, height: usize, rgba: &[u8], reference: &Path) -> Result<usize, String> {
+    let bytes = fs::read(reference).map_err(|e| format!("{}: {}", reference.display(), e))?;
+    let (ref_width, ref_height, expected) = decode_png(&bytes)?;
+    if (ref_width, ref_height) != (width, height) {
+        return Err(format!("Image is {width}x{height}, reference is {ref_width}x{ref_height}"));
+    }
+    Ok(rgba.chunks(4).zip(expected.chunks(4)).filter(|(a, b)| a != b).count())
+}
+
+// Nearest neighbour integer upscale of a RGBA8 buffer
+pub fn scale_rgba(width: usize, height: usize, rgba: &[u8], scale: usize) -> Vec<u8> {
+    if scale <= 1 {
+        return rgba.to_vec();
+    }
+    let mut out = Vec::with_capacity(rgba.len() * scale * scale);
+    for y in 0..height {
+        let row = &rgba[y * width * 4..(y + 1) * width * 4];
+        let mut scaled_row = Vec::with_capacity(row.len() * scale);
+        for pixel in row.chunks(4) {
+            for _ in 0..scale {
+                scaled_row.extend_from_slice(pixel);
+            }
+        }
+        for _ in 0..scale {
+            out.extend_from_slice(&scaled_row);
+        }
+    }
+    out
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    // crc covers chunk type and data
+    let crc = crc32(&[kind.as_slice(), data].concat());
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+// zlib stream made of stored deflate blocks
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    // deflate, 32k window, no dictionary, fastest
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(STORED_BLOCK_SIZE).peekable();
+    if blocks.peek().is_none() {
+        // empty final block
+        out.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let len = block.len() as u16;
+        out.push(last as u8);
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+// Data of a zlib stream made of stored deflate blocks
+fn zlib_unstored(stream: &[u8]) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    let mut offset = 2;
+    loop {
+        let header = *stream.get(offset).ok_or("Truncated PNG image data")?;
+        if header & 0b110 != 0 {
+            return Err(String::from("Compressed PNGs are not supported"));
+        }
+        let len = stream.get(offset + 1..offset + 3).ok_or("Truncated PNG image data")?;
+        let len = u16::from_le_bytes([len[0], len[1]]) as usize;
+        let block = stream.get(offset + 5..offset + 5 + len).ok_or("Truncated PNG image data")?;
+        out.extend_from_slice(block);
+        offset += 5 + len;
+        if header & 1 != 0 {
+            return Ok(out);
+        }
+    }
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = ((p - a as i16).abs(), (p - b as i16).abs(), (p - c as i16).abs());
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let mut a = 1u32;
+    let mut b = 0u32;
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
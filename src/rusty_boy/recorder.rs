@@ -0,0 +1,64 @@
+use std::path::Path;
+use avi::AviWriter;
+use gif::GifWriter;
+pub use vgm::{VgmLog, VgmOptions, VgmWrite};
+pub use wav::{WavFormat, WavRecorder, WavSource};
+
+mod avi;
+mod gif;
+mod vgm;
+mod wav;
+
+// Emulated frame timing, one frame is 70224 cycles at 4194304 hz
+const CPU_CLOCK: u32 = 4194304;
+const CYCLES_PER_FRAME: u32 = 70224;
+
+#[derive(PartialEq, Clone, Copy, Debug, Eq)]
+pub enum RecordFormat {
+    // uncompressed video and 16 bit pcm audio
+    Avi,
+    // animated gif without audio, for short clips
+    Gif,
+}
+
+impl RecordFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            RecordFormat::Avi => "avi",
+            RecordFormat::Gif => "gif",
+        }
+    }
+}
+
+// Gameplay recorder, frames and samples are timed by emulated frames rather than wall time
+pub enum Recorder {
+    Avi(AviWriter),
+    Gif(GifWriter),
+}
+
+impl Recorder {
+    pub fn new(path: &Path, format: RecordFormat) -> Result<Self, String> {
+        match format {
+            RecordFormat::Avi => Ok(Recorder::Avi(AviWriter::new(path, 160, 144)?)),
+            RecordFormat::Gif => Ok(Recorder::Gif(GifWriter::new(path, 160, 144)?)),
+        }
+    }
+    // true if the recorder wants the audio samples of each frame
+    pub fn records_audio(&self) -> bool {
+        matches!(self, Recorder::Avi(_))
+    }
+    // adds one emulated frame of RGBA video and the interleaved stereo samples produced during it
+    pub fn add_frame(&mut self, rgba: &[u8], samples: &[f32]) -> Result<(), String> {
+        match self {
+            Recorder::Avi(x) => x.add_frame(rgba, samples),
+            Recorder::Gif(x) => x.add_frame(rgba),
+        }
+    }
+    // writes out the headers and closes the file
+    pub fn finish(self) -> Result<(), String> {
+        match self {
+            Recorder::Avi(x) => x.finish(),
+            Recorder::Gif(x) => x.finish(),
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+use crate::rusty_boy::recorder::{CPU_CLOCK, CYCLES_PER_FRAME};
+use crate::rusty_boy::RECORD_SAMPLE_RATE;
+
+// avi index flag for keyframes, every uncompressed frame is one
+const AVIIF_KEYFRAME: u32 = 0x10;
+// avi main header flag, file has an idx1 index
+const AVIF_HASINDEX: u32 = 0x10;
+// largest file written, all avi 1.0 sizes are 32 bit and many players read them as signed
+const MAX_FILE_SIZE: u64 = i32::MAX as u64;
+
+// Uncompressed avi writer, 24 bit DIB video stream and 16 bit stereo pcm audio stream
+pub struct AviWriter {
+    file: BufWriter<File>,
+    width: u32,
+    height: u32,
+    // idx1 entries (chunk id, offset from movi, size)
+    index: Vec<([u8; 4], u32, u32)>,
+    // bytes written into the movi list after its fourcc
+    movi_size: u32,
+    frames: u32,
+    // stereo sample pairs
+    samples: u32,
+}
+
+impl AviWriter {
+    pub fn new(path: &Path, width: u32, height: u32) -> Result<Self, String> {
+        let file = File::create(path).map_err(|e| e.to_string())?;
+        let mut this = Self {
+            file: BufWriter::new(file),
+            width,
+            height,
+            index: Vec::new(),
+            movi_size: 0,
+            frames: 0,
+            samples: 0,
+        };
+        // placeholder, real header is written on finish once the lengths are known
+        let placeholder = vec![0; this.header(0, 0).len()];
+        this.file.write_all(&placeholder).map_err(|e| e.to_string())?;
+        Ok(this)
+    }
+
+    // Adds a frame, fails without writing anything once the frame would take the file past MAX_FILE_SIZE.
+    // The file written so far stays valid and is closed by finish
+    pub fn add_frame(&mut self, rgba: &[u8], samples: &[f32]) -> Result<(), String> {
+        // DIB rows are bottom up in BGR order
+        let mut bgr = Vec::with_capacity((self.width * self.height * 3) as usize);
+        for row in rgba.chunks((self.width * 4) as usize).rev() {
+            for pixel in row.chunks(4) {
+                bgr.extend_from_slice(&[pixel[2], pixel[1], pixel[0]]);
+            }
+        }
+        let mut pcm = Vec::with_capacity(samples.len() * 2);
+        for sample in samples {
+            let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+            pcm.extend_from_slice(&value.to_le_bytes());
+        }
+
+        let chunks = [chunk(*b"00db", &bgr), chunk(*b"01wb", &pcm)];
+        let chunks = if pcm.is_empty() { &chunks[..1] } else { &chunks[..] };
+        let added: u64 = chunks.iter().map(|data| data.len() as u64).sum();
+        if self.file_size(added, chunks.len()) > MAX_FILE_SIZE {
+            return Err(String::from("reached the 2 GiB AVI file size limit"));
+        }
+        for data in chunks {
+            self.write_chunk(data)?;
+        }
+        self.frames += 1;
+        self.samples += samples.len() as u32 / 2;
+        Ok(())
+    }
+
+    // File size after adding chunks to the movi list, including the idx1 index
+    fn file_size(&self, added_bytes: u64, added_chunks: usize) -> u64 {
+        let index_size = 8 + 16 * (self.index.len() + added_chunks) as u64;
+        self.header(0, 0).len() as u64 + self.movi_size as u64 + added_bytes + index_size
+    }
+
+    pub fn finish(mut self) -> Result<(), String> {
+        // idx1 offsets are relative to the movi fourcc
+        let mut idx1 = Vec::with_capacity(self.index.len() * 16);
+        for (id, offset, size) in &self.index {
+            idx1.extend_from_slice(id);
+            idx1.extend_from_slice(&AVIIF_KEYFRAME.to_le_bytes());
+            idx1.extend_from_slice(&offset.to_le_bytes());
+            idx1.extend_from_slice(&size.to_le_bytes());
+        }
+        let idx1 = chunk(*b"idx1", &idx1);
+        self.file.write_all(&idx1).map_err(|e| e.to_string())?;
+
+        // the samples come from a fixed rate capture timed by emulated cycles, so both streams stay in sync
+        let riff_size = (self.file_size(0, 0) - 8) as u32;
+        let header = self.header(RECORD_SAMPLE_RATE, riff_size);
+
+        self.file.seek(SeekFrom::Start(0)).map_err(|e| e.to_string())?;
+        self.file.write_all(&header).map_err(|e| e.to_string())?;
+        self.file.flush().map_err(|e| e.to_string())
+    }
+
+    // writes a chunk made by chunk() into the movi list and indexes it
+    fn write_chunk(&mut self, data: &[u8]) -> Result<(), String> {
+        let id = data[0..4].try_into().unwrap();
+        let size = u32::from_le_bytes(data[4..8].try_into().unwrap());
+        // + 4 to skip the movi fourcc
+        self.index.push((id, self.movi_size + 4, size));
+        self.movi_size += data.len() as u32;
+        self.file.write_all(data).map_err(|e| e.to_string())
+    }
+
+    // RIFF header up to and including the movi list fourcc
+    fn header(&self, sample_rate: u32, riff_size: u32) -> Vec<u8> {
+        let frame_size = self.width * self.height * 3;
+
+        // main header
+        let mut avih = Vec::new();
+        push_u32s(&mut avih, &[
+            (1_000_000u64 * CYCLES_PER_FRAME as u64 / CPU_CLOCK as u64) as u32,
+            frame_size * 60 + sample_rate * 4,
+            0,
+            AVIF_HASINDEX,
+            self.frames,
+            0,
+            2,
+            frame_size,
+            self.width,
+            self.height,
+            0, 0, 0, 0,
+        ]);
+
+        // video stream, rate / scale gives the exact gameboy refresh rate
+        let mut vids = b"vidsDIB ".to_vec();
+        push_u32s(&mut vids, &[0, 0, 0, CYCLES_PER_FRAME, CPU_CLOCK, 0, self.frames, frame_size, u32::MAX, 0]);
+        push_u16s(&mut vids, &[0, 0, self.width as u16, self.height as u16]);
+        let mut bitmap = Vec::new();
+        push_u32s(&mut bitmap, &[40, self.width, self.height]);
+        push_u16s(&mut bitmap, &[1, 24]);
+        push_u32s(&mut bitmap, &[0, frame_size, 0, 0, 0, 0]);
+        let video_list = list(*b"strl", &[chunk(*b"strh", &vids), chunk(*b"strf", &bitmap)].concat());
+
+        // audio stream, 16 bit stereo so one block is 4 bytes
+        let mut auds = b"auds".to_vec();
+        push_u32s(&mut auds, &[0, 0, 0, 0, 4, sample_rate * 4, 0, self.samples, sample_rate, u32::MAX, 4]);
+        push_u16s(&mut auds, &[0, 0, 0, 0]);
+        let mut wave = Vec::new();
+        push_u16s(&mut wave, &[1, 2]);
+        push_u32s(&mut wave, &[sample_rate, sample_rate * 4]);
+        push_u16s(&mut wave, &[4, 16, 0]);
+        let audio_list = list(*b"strl", &[chunk(*b"strh", &auds), chunk(*b"strf", &wave)].concat());
+
+        let hdrl = list(*b"hdrl", &[chunk(*b"avih", &avih), video_list, audio_list].concat());
+
+        let mut header = b"RIFF".to_vec();
+        header.extend_from_slice(&riff_size.to_le_bytes());
+        header.extend_from_slice(b"AVI ");
+        header.extend_from_slice(&hdrl);
+        header.extend_from_slice(b"LIST");
+        header.extend_from_slice(&(self.movi_size + 4).to_le_bytes());
+        header.extend_from_slice(b"movi");
+        header
+    }
+}
+
+fn chunk(id: [u8; 4], data: &[u8]) -> Vec<u8> {
+    let mut out = id.to_vec();
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out.extend_from_slice(data);
+    // chunks are word aligned
+    if data.len() % 2 != 0 {
+        out.push(0);
+    }
+    out
+}
+
+fn list(kind: [u8; 4], content: &[u8]) -> Vec<u8> {
+    let mut out = b"LIST".to_vec();
+    out.extend_from_slice(&(content.len() as u32 + 4).to_le_bytes());
+    out.extend_from_slice(&kind);
+    out.extend_from_slice(content);
+    out
+}
+
+fn push_u32s(out: &mut Vec<u8>, values: &[u32]) {
+    for value in values {
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+}
+
+fn push_u16s(out: &mut Vec<u8>, values: &[u16]) {
+    for value in values {
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+}
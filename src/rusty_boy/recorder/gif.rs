@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use crate::rusty_boy::recorder::{CPU_CLOCK, CYCLES_PER_FRAME};
+
+// largest lzw code in a gif stream
+const MAX_CODE: u16 = 4095;
+
+// Animated gif writer, keeps every other emulated frame since most viewers clamp delays under 2/100 s
+pub struct GifWriter {
+    file: BufWriter<File>,
+    width: u16,
+    height: u16,
+    frames: u32,
+    // total delay written so far, in 1/100 s
+    written_cs: u32,
+}
+
+impl GifWriter {
+    pub fn new(path: &Path, width: u16, height: u16) -> Result<Self, String> {
+        let file = File::create(path).map_err(|e| e.to_string())?;
+        let mut this = Self {
+            file: BufWriter::new(file),
+            width,
+            height,
+            frames: 0,
+            written_cs: 0,
+        };
+
+        let mut header = b"GIF89a".to_vec();
+        // logical screen, no global color table
+        header.extend_from_slice(&width.to_le_bytes());
+        header.extend_from_slice(&height.to_le_bytes());
+        header.extend_from_slice(&[0, 0, 0]);
+        // loop forever
+        header.extend_from_slice(&[0x21, 0xff, 0x0b]);
+        header.extend_from_slice(b"NETSCAPE2.0");
+        header.extend_from_slice(&[0x03, 0x01, 0x00, 0x00, 0x00]);
+        this.file.write_all(&header).map_err(|e| e.to_string())?;
+        Ok(this)
+    }
+
+    pub fn add_frame(&mut self, rgba: &[u8]) -> Result<(), String> {
+        let frame = self.frames;
+        self.frames += 1;
+        if frame % 2 != 0 {
+            return Ok(());
+        }
+
+        // delay until the next kept frame, rounded on emulated time so it never drifts
+        let end_cs = ((frame + 2) as u64 * CYCLES_PER_FRAME as u64 * 100 + CPU_CLOCK as u64 / 2) / CPU_CLOCK as u64;
+        let delay = end_cs as u32 - self.written_cs;
+        self.written_cs = end_cs as u32;
+
+        let (palette, indices) = quantize(rgba);
+        // color table size is a power of 2, at least 2 entries
+        let bits = (usize::BITS - (palette.len() - 1).max(1).leading_zeros()) as u8;
+
+        let mut out = Vec::new();
+        // graphic control extension
+        out.extend_from_slice(&[0x21, 0xf9, 0x04, 0x00]);
+        out.extend_from_slice(&(delay as u16).to_le_bytes());
+        out.extend_from_slice(&[0x00, 0x00]);
+        // image descriptor with a local color table
+        out.push(0x2c);
+        out.extend_from_slice(&[0, 0, 0, 0]);
+        out.extend_from_slice(&self.width.to_le_bytes());
+        out.extend_from_slice(&self.height.to_le_bytes());
+        out.push(0x80 | (bits - 1));
+        for i in 0..1 << bits {
+            out.extend_from_slice(&palette.get(i).copied().unwrap_or([0; 3]));
+        }
+
+        // lzw image data in sub blocks of up to 255 bytes
+        let min_code_size = bits.max(2);
+        out.push(min_code_size);
+        for block in lzw_encode(&indices, min_code_size).chunks(255) {
+            out.push(block.len() as u8);
+            out.extend_from_slice(block);
+        }
+        out.push(0);
+
+        self.file.write_all(&out).map_err(|e| e.to_string())
+    }
+
+    pub fn finish(mut self) -> Result<(), String> {
+        self.file.write_all(&[0x3b]).map_err(|e| e.to_string())?;
+        self.file.flush().map_err(|e| e.to_string())
+    }
+}
+
+// Builds an exact palette, falls back to rgb 3-3-2 when the frame has more than 256 colors
+fn quantize(rgba: &[u8]) -> (Vec<[u8; 3]>, Vec<u8>) {
+    let mut palette: Vec<[u8; 3]> = Vec::new();
+    let mut lookup: HashMap<[u8; 3], u8> = HashMap::new();
+    let mut indices = Vec::with_capacity(rgba.len() / 4);
+    for pixel in rgba.chunks(4) {
+        let color = [pixel[0], pixel[1], pixel[2]];
+        match lookup.get(&color) {
+            Some(index) => indices.push(*index),
+            None => {
+                if palette.len() == 256 {
+                    return quantize_332(rgba);
+                }
+                lookup.insert(color, palette.len() as u8);
+                indices.push(palette.len() as u8);
+                palette.push(color);
+            }
+        }
+    }
+    (palette, indices)
+}
+
+fn quantize_332(rgba: &[u8]) -> (Vec<[u8; 3]>, Vec<u8>) {
+    let palette = (0..=255u16)
+        .map(|i| [((i >> 5) * 255 / 7) as u8, (((i >> 2) & 0b111) * 255 / 7) as u8, ((i & 0b11) * 255 / 3) as u8])
+        .collect();
+    let indices = rgba
+        .chunks(4)
+        .map(|pixel| (pixel[0] & 0xe0) | ((pixel[1] >> 3) & 0x1c) | (pixel[2] >> 6))
+        .collect();
+    (palette, indices)
+}
+
+// Variable width lzw as used by gif, codes are packed least significant bit first
+fn lzw_encode(indices: &[u8], min_code_size: u8) -> Vec<u8> {
+    let clear = 1u16 << min_code_size;
+    let end = clear + 1;
+    let mut out = Vec::new();
+    let mut acc: u32 = 0;
+    let mut acc_bits = 0;
+    let mut code_size = min_code_size + 1;
+    let mut next_code = end + 1;
+    let mut dict: HashMap<(u16, u8), u16> = HashMap::new();
+
+    // writes a code then widens the code size once the decoder's table would outgrow it
+    let mut emit = |code: u16, code_size: &mut u8, next_code: u16| {
+        acc |= (code as u32) << acc_bits;
+        acc_bits += *code_size;
+        while acc_bits >= 8 {
+            out.push(acc as u8);
+            acc >>= 8;
+            acc_bits -= 8;
+        }
+        if next_code >= 1 << *code_size && *code_size < 12 {
+            *code_size += 1;
+        }
+    };
+
+    emit(clear, &mut code_size, next_code);
+    let mut prefix: Option<u16> = None;
+    for &index in indices {
+        let Some(current) = prefix else {
+            prefix = Some(index as u16);
+            continue;
+        };
+        if let Some(&code) = dict.get(&(current, index)) {
+            prefix = Some(code);
+            continue;
+        }
+        emit(current, &mut code_size, next_code);
+        if next_code >= MAX_CODE {
+            // table full, start over
+            emit(clear, &mut code_size, next_code);
+            dict.clear();
+            next_code = end + 1;
+            code_size = min_code_size + 1;
+        } else {
+            dict.insert((current, index), next_code);
+            next_code += 1;
+        }
+        prefix = Some(index as u16);
+    }
+    if let Some(current) = prefix {
+        emit(current, &mut code_size, next_code);
+    }
+    emit(end, &mut code_size, next_code);
+    if acc_bits > 0 {
+        out.push(acc as u8);
+    }
+    out
+}
@@ -0,0 +1,185 @@
+// VGM 1.61 export of apu register writes, for chiptune archiving. Every write to ff10-ff3f is
+// logged with its cycle and replayed by vgm players on their own Game Boy DMG core
+use std::fs;
+use std::path::Path;
+
+const CPU_CLOCK: u64 = 4194304;
+// vgm timing is always in 44100 hz samples
+const VGM_RATE: u64 = 44100;
+const HEADER_SIZE: usize = 0x100;
+const VERSION: u32 = 0x161;
+
+// Loop options for the exported file
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct VgmOptions {
+    // look for a repeating section at the end of the log
+    pub detect_loop: bool,
+    // shortest section accepted as the loop, avoids looping a single repeated bar
+    pub min_loop_secs: f32,
+}
+
+impl VgmOptions {
+    pub fn new() -> Self {
+        Self {
+            detect_loop: true,
+            min_loop_secs: 10.0,
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Copy, Debug, Eq)]
+pub struct VgmWrite {
+    pub cycle: u64,
+    // offset from ff10
+    pub register: u8,
+    pub value: u8,
+}
+
+// Register writes between the start and end of logging
+pub struct VgmLog {
+    pub start: u64,
+    pub end: u64,
+    pub writes: Vec<VgmWrite>,
+    // loop start set by hand, takes priority over detection
+    pub loop_mark: Option<u64>,
+}
+
+impl VgmLog {
+    pub fn new(start: u64) -> Self {
+        Self {
+            start,
+            end: start,
+            writes: Vec::new(),
+            loop_mark: None,
+        }
+    }
+
+    pub fn save(&self, path: &Path, options: VgmOptions) -> Result<(), String> {
+        fs::write(path, self.encode(options)).map_err(|e| e.to_string())
+    }
+
+    pub fn encode(&self, options: VgmOptions) -> Vec<u8> {
+        let events: Vec<(u64, u8, u8)> = self
+            .writes
+            .iter()
+            .map(|w| (self.sample(w.cycle), w.register, w.value))
+            .collect();
+        let end = self.sample(self.end);
+
+        // (first looped event, loop start time), events after the loop are dropped
+        let (events, end, loop_point) = match self.loop_mark {
+            Some(mark) => {
+                let time = self.sample(mark);
+                let index = events.iter().position(|e| e.0 >= time).unwrap_or(events.len());
+                (&events[..], end, Some((index, time)))
+            }
+            None => match options.detect_loop.then(|| find_loop(&events, (options.min_loop_secs * VGM_RATE as f32) as u64)).flatten() {
+                Some((start, period)) => {
+                    let loop_end = match events.get(start + period) {
+                        Some(event) => event.0,
+                        None => end,
+                    };
+                    (&events[..start + period], loop_end, Some((start, events[start].0)))
+                }
+                None => (&events[..], end, None),
+            },
+        };
+
+        let mut data = Vec::new();
+        let mut now = 0;
+        let mut loop_offset = None;
+        for (index, (time, register, value)) in events.iter().enumerate() {
+            if let Some((_, loop_time)) = loop_point.filter(|(loop_index, _)| *loop_index == index) {
+                push_wait(&mut data, loop_time - now);
+                now = loop_time;
+                loop_offset = Some(HEADER_SIZE + data.len());
+            }
+            push_wait(&mut data, time - now);
+            now = *time;
+            // b3 aa dd, game boy dmg write
+            data.extend_from_slice(&[0xb3, *register, *value]);
+        }
+        // a loop can start after the last write
+        if let Some((_, loop_time)) = loop_point.filter(|(loop_index, _)| *loop_index == events.len()) {
+            push_wait(&mut data, loop_time.saturating_sub(now));
+            now = now.max(loop_time);
+            loop_offset = Some(HEADER_SIZE + data.len());
+        }
+        push_wait(&mut data, end.saturating_sub(now));
+        data.push(0x66);
+
+        let mut header = vec![0u8; HEADER_SIZE];
+        let mut put = |offset: usize, value: u32| header[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        put(0x00, u32::from_le_bytes(*b"Vgm "));
+        put(0x04, (HEADER_SIZE + data.len() - 0x04) as u32);
+        put(0x08, VERSION);
+        put(0x18, end.max(now) as u32);
+        if let (Some(offset), Some((_, loop_time))) = (loop_offset, loop_point) {
+            // offsets are relative to their own field
+            put(0x1c, (offset - 0x1c) as u32);
+            put(0x20, (end - loop_time) as u32);
+        }
+        put(0x34, (HEADER_SIZE - 0x34) as u32);
+        put(0x80, CPU_CLOCK as u32);
+
+        header.extend_from_slice(&data);
+        header
+    }
+
+    // cycle to vgm sample time since the start of logging
+    fn sample(&self, cycle: u64) -> u64 {
+        (cycle.saturating_sub(self.start)) * VGM_RATE / CPU_CLOCK
+    }
+}
+
+// Wait commands for a number of 44100 hz samples
+fn push_wait(data: &mut Vec<u8>, mut samples: u64) {
+    while samples > 0 {
+        match samples {
+            1..=16 => {
+                data.push(0x70 + (samples - 1) as u8);
+                samples = 0;
+            }
+            735 => {
+                data.push(0x62);
+                samples = 0;
+            }
+            882 => {
+                data.push(0x63);
+                samples = 0;
+            }
+            _ => {
+                let wait = samples.min(0xffff);
+                data.push(0x61);
+                data.extend_from_slice(&(wait as u16).to_le_bytes());
+                samples -= wait;
+            }
+        }
+    }
+}
+
+// Finds a section that repeats until the end of the log, at least min_samples long and heard
+// twice in full. Returns the first event of the loop and its length in events
+fn find_loop(events: &[(u64, u8, u8)], min_samples: u64) -> Option<(usize, usize)> {
+    let n = events.len();
+    // delay before each event, rounding can move a write by a sample between repeats
+    let delay = |i: usize| if i == 0 { events[0].0 } else { events[i].0 - events[i - 1].0 };
+    let same = |a: usize, b: usize| {
+        events[a].1 == events[b].1 && events[a].2 == events[b].2 && delay(a).abs_diff(delay(b)) <= 1
+    };
+
+    for period in 1..=n / 2 {
+        if events[n - 1].0 - events[n - 1 - period].0 < min_samples {
+            continue;
+        }
+        // how far back the log keeps repeating with this period
+        let mut repeated = 0;
+        while repeated < n - period && same(n - 1 - period - repeated, n - 1 - repeated) {
+            repeated += 1;
+        }
+        if repeated >= period {
+            return Some((n - period - repeated, period));
+        }
+    }
+    None
+}
@@ -0,0 +1,184 @@
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+// wav format tags
+const WAVE_FORMAT_PCM: u16 = 1;
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+
+#[derive(PartialEq, Clone, Copy, Debug, Eq)]
+pub enum WavFormat {
+    Pcm16,
+    Float32,
+}
+
+impl WavFormat {
+    pub const ALL: [WavFormat; 2] = [WavFormat::Pcm16, WavFormat::Float32];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            WavFormat::Pcm16 => "16 bit",
+            WavFormat::Float32 => "32 bit float",
+        }
+    }
+    fn bytes_per_sample(&self) -> u16 {
+        match self {
+            WavFormat::Pcm16 => 2,
+            WavFormat::Float32 => 4,
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Copy, Debug, Eq)]
+pub enum WavSource {
+    // final stereo output
+    Mix,
+    // one mono file per channel, taken before nr51 panning
+    Stems,
+}
+
+impl WavSource {
+    pub const ALL: [WavSource; 2] = [WavSource::Mix, WavSource::Stems];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            WavSource::Mix => "Stereo mix",
+            WavSource::Stems => "Channel stems",
+        }
+    }
+    // interleaved channels the apu hands over for this source
+    pub fn channels(&self) -> usize {
+        match self {
+            WavSource::Mix => 2,
+            WavSource::Stems => 4,
+        }
+    }
+}
+
+// Audio recorder writing either one stereo wav or four mono channel wavs
+pub struct WavRecorder {
+    writers: Vec<WavWriter>,
+}
+
+impl WavRecorder {
+    // Files are named after base, stems get a _ch1 to _ch4 suffix
+    pub fn new(base: &Path, source: WavSource, format: WavFormat, sample_rate: u32) -> Result<Self, String> {
+        let writers = match source {
+            WavSource::Mix => vec![WavWriter::new(&base.with_extension("wav"), 2, format, sample_rate)?],
+            WavSource::Stems => (1..=4)
+                .map(|n| {
+                    let name = format!("{}_ch{}.wav", base.file_name().unwrap_or_default().to_string_lossy(), n);
+                    WavWriter::new(&base.with_file_name(name), 1, format, sample_rate)
+                })
+                .collect::<Result<_, _>>()?,
+        };
+        Ok(Self { writers })
+    }
+
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.writers.iter().map(|w| w.path.clone()).collect()
+    }
+
+    // adds samples interleaved by source channel, stems are split into their files
+    pub fn add_samples(&mut self, samples: &[f32]) -> Result<(), String> {
+        if self.writers.len() == 1 {
+            return self.writers[0].add_samples(samples);
+        }
+        let count = self.writers.len();
+        for (n, writer) in self.writers.iter_mut().enumerate() {
+            let channel: Vec<f32> = samples.iter().skip(n).step_by(count).copied().collect();
+            writer.add_samples(&channel)?;
+        }
+        Ok(())
+    }
+
+    // writes out the headers and closes the files
+    pub fn finish(self) -> Result<(), String> {
+        for writer in self.writers {
+            writer.finish()?;
+        }
+        Ok(())
+    }
+}
+
+// Streaming wav writer, the header sizes are filled in on finish
+pub struct WavWriter {
+    file: BufWriter<File>,
+    path: PathBuf,
+    channels: u16,
+    format: WavFormat,
+    sample_rate: u32,
+    data_size: u32,
+}
+
+impl WavWriter {
+    pub fn new(path: &Path, channels: u16, format: WavFormat, sample_rate: u32) -> Result<Self, String> {
+        let file = File::create(path).map_err(|e| e.to_string())?;
+        let mut this = Self {
+            file: BufWriter::new(file),
+            path: path.to_path_buf(),
+            channels,
+            format,
+            sample_rate,
+            data_size: 0,
+        };
+        let header = this.header();
+        this.file.write_all(&header).map_err(|e| e.to_string())?;
+        Ok(this)
+    }
+
+    pub fn add_samples(&mut self, samples: &[f32]) -> Result<(), String> {
+        let mut data = Vec::with_capacity(samples.len() * self.format.bytes_per_sample() as usize);
+        for sample in samples {
+            match self.format {
+                WavFormat::Pcm16 => {
+                    let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+                    data.extend_from_slice(&value.to_le_bytes());
+                }
+                WavFormat::Float32 => data.extend_from_slice(&sample.to_le_bytes()),
+            }
+        }
+        self.data_size += data.len() as u32;
+        self.file.write_all(&data).map_err(|e| e.to_string())
+    }
+
+    pub fn finish(mut self) -> Result<(), String> {
+        // data chunks are word aligned
+        if self.data_size % 2 != 0 {
+            self.file.write_all(&[0]).map_err(|e| e.to_string())?;
+        }
+        let header = self.header();
+        self.file.seek(SeekFrom::Start(0)).map_err(|e| e.to_string())?;
+        self.file.write_all(&header).map_err(|e| e.to_string())?;
+        self.file.flush().map_err(|e| e.to_string())
+    }
+
+    // RIFF header up to the start of the sample data
+    fn header(&self) -> Vec<u8> {
+        let bytes_per_sample = self.format.bytes_per_sample();
+        let block_align = self.channels * bytes_per_sample;
+        let tag = match self.format {
+            WavFormat::Pcm16 => WAVE_FORMAT_PCM,
+            WavFormat::Float32 => WAVE_FORMAT_IEEE_FLOAT,
+        };
+
+        let mut fmt = Vec::new();
+        fmt.extend_from_slice(&tag.to_le_bytes());
+        fmt.extend_from_slice(&self.channels.to_le_bytes());
+        fmt.extend_from_slice(&self.sample_rate.to_le_bytes());
+        fmt.extend_from_slice(&(self.sample_rate * block_align as u32).to_le_bytes());
+        fmt.extend_from_slice(&block_align.to_le_bytes());
+        fmt.extend_from_slice(&(bytes_per_sample * 8).to_le_bytes());
+
+        let mut header = b"RIFF".to_vec();
+        let riff_size = 4 + (8 + fmt.len() as u32) + 8 + self.data_size + self.data_size % 2;
+        header.extend_from_slice(&riff_size.to_le_bytes());
+        header.extend_from_slice(b"WAVE");
+        header.extend_from_slice(b"fmt ");
+        header.extend_from_slice(&(fmt.len() as u32).to_le_bytes());
+        header.extend_from_slice(&fmt);
+        header.extend_from_slice(b"data");
+        header.extend_from_slice(&self.data_size.to_le_bytes());
+        header
+    }
+}
@@ -1,9 +1,62 @@
 use crate::rusty_boy::screen::s_registers::{Palette, TileCache, LCDC, STAT};
+use crate::rusty_boy::screen::theme::Theme;
 use std::collections::BTreeMap;
 use std::rc::Weak;
 use crate::rusty_boy::motherboard::Motherboard;
 
 mod s_registers;
+pub mod theme;
+
+// Palette used by the debug viewers
+#[derive(PartialEq, Clone, Copy, Debug, Eq)]
+pub enum ViewPalette {
+    Bgp,
+    Obp0,
+    Obp1,
+    // raw color indices as grey shades
+    Grey,
+}
+
+// Layers that can be hidden or tinted for debugging
+#[derive(PartialEq, Clone, Copy, Debug, Eq)]
+pub enum Layer {
+    Background,
+    Window,
+    Sprites,
+}
+
+impl Layer {
+    pub const ALL: [Layer; 3] = [Layer::Background, Layer::Window, Layer::Sprites];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Layer::Background => "Background",
+            Layer::Window => "Window",
+            Layer::Sprites => "Sprites",
+        }
+    }
+    // color mixed into the layer's pixels when tinting is on
+    pub fn tint_color(&self) -> [u8; 3] {
+        match self {
+            Layer::Background => [255, 64, 64],
+            Layer::Window => [64, 220, 64],
+            Layer::Sprites => [64, 96, 255],
+        }
+    }
+}
+
+// Debug switches for one layer
+#[derive(PartialEq, Clone, Copy, Debug, Eq)]
+pub struct LayerOptions {
+    pub visible: bool,
+    pub tint: bool,
+}
+
+impl LayerOptions {
+    pub fn new() -> Self {
+        Self { visible: true, tint: false }
+    }
+}
 
 pub struct Screen {
     pub vram: [u8; 0x2000],
@@ -31,6 +84,13 @@ pub struct Screen {
     pub tile_cache: TileCache,
     pub frame_done: bool,
 
+    // lcd ghosting, how much of the previous frame persists into the new one (0 is off)
+    pub ghosting: f32,
+    // previous blended frame RGBA
+    last_frame: Vec<u8>,
+    // debug layer switches, indexed by Layer
+    pub layers: [LayerOptions; 3],
+
     // motherboard pointer
     motherboard: Weak<Motherboard>
 }
@@ -59,6 +119,9 @@ impl Screen {
             screen_buffer_color: [0; 160 * 144],
             tile_cache: TileCache::new(),
             frame_done: false,
+            ghosting: 0.0,
+            last_frame: [0; 160 * 144 * 4].to_vec(),
+            layers: [LayerOptions::new(); 3],
             motherboard: mb
         }
     }
@@ -128,6 +191,7 @@ impl Screen {
                             println!("Could not upgrade, motherboard does not exist??");
                         }
                         // Frame finished 
+                        self.blend_frame();
                         self.frame_done = true;
                     }
                 }
@@ -188,13 +252,19 @@ impl Screen {
             _ => unreachable!()
         }
     }
-    fn set_pixel_color(&mut self, x: u8, y: u8, color: u8, color_index: Option<u8>) {
+    // Applies the theme colors to all three palettes, takes effect on the next scanline
+    pub fn set_theme(&mut self, theme: &Theme) {
+        self.bgp.set_colors(theme.bg);
+        self.obp0.set_colors(theme.obp0);
+        self.obp1.set_colors(theme.obp1);
+    }
+    fn set_pixel_color(&mut self, x: u8, y: u8, color: [u8; 3], color_index: Option<u8>) {
         let offset = (y as usize * 160 + x as usize) * 4;
         // Sets screen buffer pixel color
         // RGB
-        self.screen_buffer[offset] = color;
-        self.screen_buffer[offset + 1] = color;
-        self.screen_buffer[offset + 2] = color;
+        self.screen_buffer[offset] = color[0];
+        self.screen_buffer[offset + 1] = color[1];
+        self.screen_buffer[offset + 2] = color[2];
         // A
         self.screen_buffer[offset + 3] = 255;
         
@@ -206,6 +276,14 @@ impl Screen {
             None => {}
         }
     }
+    // Mixes the layer tint into a pixel color when tinting is enabled for it
+    fn layer_color(&self, layer: Layer, color: [u8; 3]) -> [u8; 3] {
+        if !self.layers[layer as usize].tint {
+            return color;
+        }
+        let tint = layer.tint_color();
+        [0, 1, 2].map(|i| ((color[i] as u16 + tint[i] as u16) / 2) as u8)
+    }
     fn draw_blank_scanline(&mut self) {
         for x in 0..160 {
             let color = self.bgp.get_color(0);
@@ -222,7 +300,7 @@ impl Screen {
             self.draw_blank_scanline();
         }
 
-        if self.lcdc.sprite_enable  {
+        if self.lcdc.sprite_enable && self.layers[Layer::Sprites as usize].visible {
             self.draw_sprite_scanline();
         }
 
@@ -242,7 +320,8 @@ impl Screen {
         let mut tile_index: usize = 0;
         for x in 0..160 {
             // within window
-            if self.lcdc.window_enable && self.wy <= self.ly && (x as i32) >= wx_comp {
+            let in_window = self.lcdc.window_enable && self.wy <= self.ly && (x as i32) >= wx_comp;
+            if in_window {
                 x_pos = (x - wx) as usize;
                 y_pos = self.wy_counter as usize;
                 offset = self.lcdc.windowmap_offset;
@@ -264,8 +343,14 @@ impl Screen {
             }
             
             // This is with caching
-            let color_index = self.tile_cache.tile_cache[tile_index * 64 + (y_pos % 8) * 8 + (x_pos % 8)];
-            let color = self.bgp.get_color(color_index);
+            let color_index = self.tile_cache.tile(tile_index)[(y_pos % 8) * 8 + (x_pos % 8)];
+            let layer = if in_window { Layer::Window } else { Layer::Background };
+            // hidden layers show color 0 but keep their index so sprite priority is unchanged
+            let color = if self.layers[layer as usize].visible {
+                self.layer_color(layer, self.bgp.get_color(color_index))
+            } else {
+                self.bgp.get_color(0)
+            };
             // This is without caching
             // tile_index = self.get_tile(x_pos, y_pos, offset);
             // let (color, color_index) = self.get_tile_bgp(tile_index, x_pos, y_pos);
@@ -273,7 +358,7 @@ impl Screen {
             self.set_pixel_color(x, self.ly, color, Some(color_index));
         }
     }
-    fn get_tile_bgp(&self, tile_index: usize, x: usize, y: usize) -> (u8, u8) {
+    fn get_tile_bgp(&self, tile_index: usize, x: usize, y: usize) -> ([u8; 3], u8) {
         let line = 2 * (y % 8);
         let pixel_index = 7 - (x % 8);
         
@@ -286,33 +371,20 @@ impl Screen {
     }
     fn draw_sprite_scanline(&mut self) {
         let spriteheight = if self.lcdc.sprite_height { 16 } else { 8 };
-        let mut spritecount = 0;
         let mut spritemap: BTreeMap<u8, Vec<usize>> = BTreeMap::new();
-        for n in (0..0xa0).step_by(4) {
-            // Init as signed to avoid overflow comparisons
-            let y = self.oam[n] as i32 - 16;
-            let ly = self.ly as i32;
+        for entry in self.sprites_on_line(self.ly).0 {
+            let n = entry * 4;
             let x = self.oam[n + 1] - 8;
-            // within sprite range
-            
-            if ly < y + spriteheight && ly >= y {
-                // insert sprite into bst to sort
-                match spritemap.get_mut(&x) {
-                    // If the vec exists, append at the end
-                    Some(arr) => {
-                        arr.push(n)
-                    }
-                    None => {
-                        // Otherwise insert vec with sprite
-                        spritemap.insert(x, vec![n]);
-                    }
+            // insert sprite into bst to sort
+            match spritemap.get_mut(&x) {
+                // If the vec exists, append at the end
+                Some(arr) => {
+                    arr.push(n)
+                }
+                None => {
+                    // Otherwise insert vec with sprite
+                    spritemap.insert(x, vec![n]);
                 }
-                spritecount += 1;
-            }
-
-            // Break from for loop if sprite count hits 10
-            if spritecount == 10 {
-                break;
             }
         }
 
@@ -360,7 +432,7 @@ impl Screen {
                     let mut color_index = ((byte2 >> index) & 1) << 1;
                     color_index |= (byte1 >> index) & 1;
                     
-                    let color: u8;
+                    let color: [u8; 3];
                     // color index 0 is transparent on sprites
                     if color_index == 0 {
                         continue;
@@ -375,6 +447,8 @@ impl Screen {
                         color = self.obp0.get_color(color_index);
                     }
                     
+                    let color = self.layer_color(Layer::Sprites, color);
+
                     // If bg prio
                     if prio {
                         if self.screen_buffer_color[self.ly as usize * 160 + xpixel as usize] == 0 {
@@ -404,6 +478,146 @@ impl Screen {
         tile_index
     }
     
+    // RGB shades of the viewer palette for color indices 0-3
+    pub fn view_colors(&self, palette: ViewPalette) -> [[u8; 3]; 4] {
+        match palette {
+            ViewPalette::Bgp => [0, 1, 2, 3].map(|i| self.bgp.get_color(i)),
+            ViewPalette::Obp0 => [0, 1, 2, 3].map(|i| self.obp0.get_color(i)),
+            ViewPalette::Obp1 => [0, 1, 2, 3].map(|i| self.obp1.get_color(i)),
+            ViewPalette::Grey => [[0xff; 3], [0xaa; 3], [0x55; 3], [0x00; 3]],
+        }
+    }
+
+    // Brings the whole tile cache up to date, the debug views below only read it
+    pub fn update_tile_cache(&mut self) {
+        self.tile_cache.update_all(&self.vram);
+    }
+
+    // Debug view of the 384 vram tiles as a 16 x 24 tile grid, RGBA 128 x 192
+    pub fn render_tile_data(&self, palette: ViewPalette) -> Vec<u8> {
+        let colors = self.view_colors(palette);
+        let mut image = vec![0xff; 128 * 192 * 4];
+        for tile in 0..384 {
+            let (tile_x, tile_y) = (tile % 16 * 8, tile / 16 * 8);
+            for (n, index) in self.tile_cache.tile(tile).iter().enumerate() {
+                let offset = ((tile_y + n / 8) * 128 + tile_x + n % 8) * 4;
+                image[offset..offset + 3].copy_from_slice(&colors[*index as usize]);
+            }
+        }
+        image
+    }
+
+    // Oam entries (0-39) that overlap the line, split into the ones drawn and the ones dropped by the 10 sprite limit
+    pub fn sprites_on_line(&self, line: u8) -> (Vec<usize>, Vec<usize>) {
+        let spriteheight = if self.lcdc.sprite_height { 16 } else { 8 };
+        let mut visible = Vec::new();
+        let mut dropped = Vec::new();
+        for entry in 0..40 {
+            // Init as signed to avoid overflow comparisons
+            let y = self.oam[entry * 4] as i32 - 16;
+            let ly = line as i32;
+            // within sprite range
+            if ly < y + spriteheight && ly >= y {
+                if visible.len() < 10 {
+                    visible.push(entry);
+                } else {
+                    dropped.push(entry);
+                }
+            }
+        }
+        (visible, dropped)
+    }
+
+    // Debug view of one oam entry with its palette and flips applied, RGBA 8 x 16
+    // color 0 is transparent, 8x8 sprites leave the bottom half transparent
+    pub fn render_sprite(&self, entry: usize) -> Vec<u8> {
+        let mut tile_index = self.oam[entry * 4 + 2] as usize;
+        let attr = self.oam[entry * 4 + 3];
+        let tall = self.lcdc.sprite_height;
+        if tall {
+            tile_index &= 0b11111110;
+        }
+        let palette = if attr & 0b10000 != 0 { ViewPalette::Obp1 } else { ViewPalette::Obp0 };
+        let colors = self.view_colors(palette);
+        let yflip = (attr >> 6) & 1 != 0;
+        let xflip = (attr >> 5) & 1 != 0;
+
+        let height = if tall { 16 } else { 8 };
+        let mut image = vec![0; 8 * 16 * 4];
+        for half in 0..height / 8 {
+            let tile = self.tile_cache.tile(tile_index + half);
+            for (n, index) in tile.iter().enumerate() {
+                if *index == 0 {
+                    continue;
+                }
+                let (mut x, mut y) = (n % 8, half * 8 + n / 8);
+                if xflip {
+                    x = 7 - x;
+                }
+                if yflip {
+                    y = height - 1 - y;
+                }
+                let offset = (y * 8 + x) * 4;
+                image[offset..offset + 3].copy_from_slice(&colors[*index as usize]);
+                image[offset + 3] = 255;
+            }
+        }
+        image
+    }
+
+    // Debug view of a full 32 x 32 tile map, RGBA 256 x 256
+    // high_map selects $9C00 instead of $9800, tiles are addressed with the current LCDC.4 mode
+    pub fn render_tilemap(&self, high_map: bool, palette: ViewPalette) -> Vec<u8> {
+        let colors = self.view_colors(palette);
+        let offset = if high_map { 0x1C00 } else { 0x1800 };
+        let mut image = vec![0xff; 256 * 256 * 4];
+        for map_y in 0..32 {
+            for map_x in 0..32 {
+                let tile = self.tile_cache.tile(self.get_tile(map_x * 8, map_y * 8, offset));
+                for (n, index) in tile.iter().enumerate() {
+                    let pixel = (map_y * 8 + n / 8) * 256 + map_x * 8 + n % 8;
+                    image[pixel * 4..pixel * 4 + 3].copy_from_slice(&colors[*index as usize]);
+                }
+            }
+        }
+        image
+    }
+
+    // Mixes the previous frame into the finished one to mimic the slow DMG lcd response
+    fn blend_frame(&mut self) {
+        if self.ghosting > 0.0 {
+            for (new, last) in self.screen_buffer.iter_mut().zip(self.last_frame.iter()) {
+                *new = (*new as f32 + (*last as f32 - *new as f32) * self.ghosting).round() as u8;
+            }
+        }
+        self.last_frame.copy_from_slice(&self.screen_buffer);
+    }
+
+    // DMG oam corruption bug, mangles the oam row the ppu is scanning in mode 2
+    pub fn corrupt_oam(&mut self, read: bool) {
+        // each row is 8 bytes, the ppu reads one row every 4 cycles of mode 2
+        let row = ((80 - self.scan_counter.clamp(0, 80)) / 4) as usize;
+        // first row is never affected
+        if row == 0 || row >= 20 {
+            return;
+        }
+        let cur = row * 8;
+        let prev = cur - 8;
+        let word = |oam: &[u8; 0xa0], index: usize| u16::from_le_bytes([oam[index], oam[index + 1]]);
+        let a = word(&self.oam, cur);
+        let b = word(&self.oam, prev);
+        let c = word(&self.oam, prev + 4);
+        let new_word = if read { b | (a & c) } else { ((a ^ c) & (b ^ c)) ^ c };
+
+        // first word is the glitched value, rest of the row is copied from the previous row
+        let [lower, upper] = new_word.to_le_bytes();
+        self.oam[cur] = lower;
+        self.oam[cur + 1] = upper;
+        for n in 2..8 {
+            self.oam[cur + n] = self.oam[prev + n];
+        }
+    }
+
     // Checks lyc == ly and triggers interrupt if needed
     fn check_lyc(&mut self) {
         let interrupt = self.stat.update_lyc(self.lyc, self.ly);
@@ -3,15 +3,15 @@ const TILE_COUNT: usize = 384;
 // Palette register
 pub struct Palette {
     value: u8,
-    lookup: [u8; 4],
-    palette: [u8; 4],
+    lookup: [[u8; 3]; 4],
+    palette: [[u8; 3]; 4],
 }
 impl Palette {
     pub fn new(value: u8) -> Self {
         let mut this = Self {
             value: 0,
-            lookup: [0; 4],
-            palette: [0xFF, 0xAA, 0x55, 0x00],
+            lookup: [[0; 3]; 4],
+            palette: [[0xFF; 3], [0xAA; 3], [0x55; 3], [0x00; 3]],
         };
         this.set(value);
         this
@@ -21,15 +21,23 @@ impl Palette {
             return false;
         }
         self.value = value;
+        self.update_lookup();
+        true
+    }
+    // swaps the rgb shades, keeps the register value
+    pub fn set_colors(&mut self, colors: [[u8; 3]; 4]) {
+        self.palette = colors;
+        self.update_lookup();
+    }
+    fn update_lookup(&mut self) {
         for x in 0..4 {
-            self.lookup[x] = self.palette[((value >> x * 2) & 0b11) as usize]
+            self.lookup[x] = self.palette[((self.value >> x * 2) & 0b11) as usize]
         }
-        true
     }
     pub fn get(&self) -> u8 {
         self.value
     }
-    pub fn get_color(&self, index: u8) -> u8 {
+    pub fn get_color(&self, index: u8) -> [u8; 3] {
         self.lookup[index as usize]
     }
 }
@@ -134,7 +142,7 @@ impl STAT {
 // Tile cache for bg for now
 pub struct TileCache {
     tile_state: [bool; TILE_COUNT], // checks which tiles are cached
-    pub tile_cache: [u8; TILE_COUNT * 8 * 8] // tile cache
+    tile_cache: [u8; TILE_COUNT * 8 * 8] // tile cache
 }
 
 impl TileCache {
@@ -169,6 +177,16 @@ impl TileCache {
         
         self.tile_state[tile_index] = true;
     }
+    // decodes every tile that changed since it was last cached
+    pub fn update_all(&mut self, vram: &[u8]) {
+        for tile_index in 0..TILE_COUNT {
+            self.update_tile(tile_index, vram);
+        }
+    }
+    // color indices of one cached tile, row by row
+    pub fn tile(&self, tile_index: usize) -> &[u8] {
+        &self.tile_cache[tile_index * 64..tile_index * 64 + 64]
+    }
     pub fn clear_cache(&mut self) {
         self.tile_state = [false; 384]
     }
@@ -0,0 +1,97 @@
+use std::fs;
+use std::path::Path;
+use serde_json::Value;
+
+// RGB shades for color indices 0-3 of each palette register
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Theme {
+    pub name: String,
+    pub bg: [[u8; 3]; 4],
+    pub obp0: [[u8; 3]; 4],
+    pub obp1: [[u8; 3]; 4],
+}
+
+impl Theme {
+    // Same shades for background and both sprite palettes
+    pub fn new(name: &str, colors: [[u8; 3]; 4]) -> Self {
+        Self {
+            name: name.to_string(),
+            bg: colors,
+            obp0: colors,
+            obp1: colors,
+        }
+    }
+
+    // Built in themes, grey first since it is the default
+    pub fn presets() -> Vec<Theme> {
+        vec![
+            Theme::new("Grey", [[0xff; 3], [0xaa; 3], [0x55; 3], [0x00; 3]]),
+            Theme::new("Classic DMG", [
+                [0x9b, 0xbc, 0x0f],
+                [0x8b, 0xac, 0x0f],
+                [0x30, 0x62, 0x30],
+                [0x0f, 0x38, 0x0f],
+            ]),
+            Theme::new("Pocket", [
+                [0xc4, 0xcf, 0xa1],
+                [0x8b, 0x95, 0x6d],
+                [0x4d, 0x53, 0x3c],
+                [0x1f, 0x1f, 0x1f],
+            ]),
+            Theme::new("Light", [
+                [0x00, 0xb5, 0x81],
+                [0x00, 0x9a, 0x71],
+                [0x00, 0x69, 0x4a],
+                [0x00, 0x4f, 0x3b],
+            ]),
+            Theme::new("High Contrast", [
+                [0xff, 0xff, 0xff],
+                [0xff, 0xe6, 0x00],
+                [0x00, 0x36, 0xa0],
+                [0x00, 0x00, 0x00],
+            ]),
+        ]
+    }
+
+    // Loads user themes from a json file, either a single theme object or an array of them
+    // { "name": "Mine", "bg": ["#ffffff", "#aaaaaa", "#555555", "#000000"], "obp0": [...], "obp1": [...] }
+    // obp0 and obp1 fall back to bg when missing
+    pub fn load(path: &Path) -> Result<Vec<Theme>, String> {
+        let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let json: Value = serde_json::from_str(&text).map_err(|e| e.to_string())?;
+        match &json {
+            Value::Array(themes) => themes.iter().map(Theme::from_json).collect(),
+            Value::Object(_) => Ok(vec![Theme::from_json(&json)?]),
+            _ => Err(String::from("Palette file must be a theme object or an array of themes")),
+        }
+    }
+
+    fn from_json(json: &Value) -> Result<Theme, String> {
+        let name = json["name"].as_str().unwrap_or("Custom").to_string();
+        let bg = parse_colors(&json["bg"])?;
+        let obp0 = if json["obp0"].is_null() { bg } else { parse_colors(&json["obp0"])? };
+        let obp1 = if json["obp1"].is_null() { bg } else { parse_colors(&json["obp1"])? };
+        Ok(Theme { name, bg, obp0, obp1 })
+    }
+}
+
+// Parses 4 "#rrggbb" strings, lightest first
+fn parse_colors(json: &Value) -> Result<[[u8; 3]; 4], String> {
+    let list = json.as_array().ok_or("Palette colors must be an array")?;
+    if list.len() != 4 {
+        return Err(String::from("Palette needs exactly 4 colors"));
+    }
+    let mut colors = [[0; 3]; 4];
+    for (i, color) in list.iter().enumerate() {
+        let text = color.as_str().ok_or("Palette color must be a string")?;
+        let hex = text.strip_prefix('#').unwrap_or(text);
+        // from_str_radix alone would also take a sign
+        if hex.len() != 6 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(format!("Invalid color {text}"));
+        }
+        let rgb = u32::from_str_radix(hex, 16).map_err(|e| e.to_string())?;
+        let [_, r, g, b] = rgb.to_be_bytes();
+        colors[i] = [r, g, b];
+    }
+    Ok(colors)
+}
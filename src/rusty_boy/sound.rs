@@ -1,9 +1,20 @@
 use rodio::{Decoder, OutputStream, source::Source};
-use rodio::buffer::SamplesBuffer;
-
-const BUFFER_SIZE: usize = 1024;
+use crate::rusty_boy::recorder::{VgmLog, VgmWrite};
+use blip::Blip;
+use capture::Capture;
+pub use capture::RECORD_SAMPLE_RATE;
+use stream::AudioQueue;
+
+mod blip;
+mod capture;
+mod stream;
+
+// past this many times the target fill the emulation runs ahead of the audio, e.g. on a high
+// refresh display, and waits
+const MAX_FILL_FACTOR: usize = 4;
+// generation rate when no output device could be opened
+const FALLBACK_SAMPLE_RATE: u32 = 48000;
  const CPU_CLOCK: usize = 4194304;
- const SAMPLE_RATE: usize = 48000;
 const WAVE_DUTY: [[u8; 8]; 4] = [
     [0, 0, 0, 0, 0, 0, 0, 1], // 12.5%
     [1, 0, 0, 0, 0, 0, 0, 1], // 25%
@@ -13,6 +24,117 @@ const WAVE_DUTY: [[u8; 8]; 4] = [
 
 const CH3_SHIFT_TABLE: [u8; 4] = [4, 0, 1, 2];
 const CH4_DIV_TABLE: [u8; 8] = [8, 16, 32, 48, 64, 80, 96, 112];
+const DUTY_NAMES: [&str; 4] = ["12.5%", "25%", "50%", "75%"];
+// cycles between oscilloscope points, 4389 points a frame
+const SCOPE_STEP: u32 = 16;
+
+// Hardware model of the analog output stage, the models differ in how fast the high pass
+// capacitor charges
+#[derive(PartialEq, Clone, Copy, Debug, Eq)]
+pub enum AudioModel {
+    Dmg,
+    Cgb,
+}
+
+impl AudioModel {
+    pub const ALL: [AudioModel; 2] = [AudioModel::Dmg, AudioModel::Cgb];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            AudioModel::Dmg => "DMG",
+            AudioModel::Cgb => "CGB",
+        }
+    }
+
+    // Capacitor charge factor per output sample, the hardware values are per cpu cycle
+    fn charge_factor(&self, sample_rate: f64) -> f32 {
+        let per_cycle: f64 = match self {
+            AudioModel::Dmg => 0.999958,
+            AudioModel::Cgb => 0.998943,
+        };
+        per_cycle.powf(CPU_CLOCK as f64 / sample_rate) as f32
+    }
+}
+
+// How much audio is buffered between the apu and the speakers. Lower is more responsive but
+// crackles on a busy or slow system
+#[derive(PartialEq, Clone, Copy, Debug, Eq)]
+pub enum AudioLatency {
+    Low,
+    Normal,
+    Conservative,
+}
+
+impl AudioLatency {
+    pub const ALL: [AudioLatency; 3] = [AudioLatency::Low, AudioLatency::Normal, AudioLatency::Conservative];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            AudioLatency::Low => "Low (10 ms)",
+            AudioLatency::Normal => "Normal (20 ms)",
+            AudioLatency::Conservative => "Conservative (60 ms)",
+        }
+    }
+
+    // time kept in the queue, the device buffer adds about half of it
+    fn millis(&self) -> u32 {
+        match self {
+            AudioLatency::Low => 10,
+            AudioLatency::Normal => 20,
+            AudioLatency::Conservative => 60,
+        }
+    }
+
+    // interleaved samples kept queued for the device
+    fn target_fill(&self, sample_rate: u32) -> usize {
+        (sample_rate * self.millis() / 1000) as usize * 2
+    }
+}
+
+// Per channel mute, solo and gain plus the master volume of the output device
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct Mixer {
+    pub mute: [bool; 4],
+    pub solo: [bool; 4],
+    pub gain: [f32; 4],
+    pub master: f32,
+}
+
+impl Mixer {
+    pub fn new() -> Self {
+        Self {
+            mute: [false; 4],
+            solo: [false; 4],
+            gain: [1.0; 4],
+            master: 0.10,
+        }
+    }
+
+    // Gain of a channel, while any channel is soloed only soloed channels are heard
+    pub fn channel_gain(&self, channel: usize) -> f32 {
+        let soloing = self.solo.iter().any(|solo| *solo);
+        if self.mute[channel] || (soloing && !self.solo[channel]) {
+            0.0
+        } else {
+            self.gain[channel]
+        }
+    }
+}
+
+// Channel state decoded from its registers, for the audio visualizer
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct ChannelStatus {
+    pub enabled: bool,
+    pub dac: bool,
+    // tone frequency in hz, the lfsr clock for noise
+    pub frequency: f32,
+    // square duty, ch3 output level or noise lfsr width
+    pub shape: &'static str,
+    // current envelope volume, 0 to 15
+    pub volume: u8,
+    // remaining length steps, None when length is disabled
+    pub length: Option<u16>,
+}
 
 fn get_wave_duty(duty: u8, duty_pos: u8) -> u8 {
     WAVE_DUTY[duty as usize][duty_pos as usize]
@@ -38,6 +160,36 @@ fn run_env(timer: &mut u8, cur_vol: &mut u8, nx2: u8) {
     }
 }
 
+// NRx4 length enable handling shared by all channels, returns false if the channel got disabled.
+// When the next frame sequencer step doesn't clock length, enabling length clocks it once extra,
+// and a trigger that reloads an expired counter loads it one short
+fn write_len_enable(len_timer: &mut u16, max: u16, old_nrx4: u8, nrx4: u8, extra_clock: bool) -> bool {
+    let was_enabled = old_nrx4 & 0x40 != 0;
+    let enabled = nrx4 & 0x40 != 0;
+    let trigger = nrx4 & 0x80 != 0;
+    let mut keep = true;
+    if extra_clock && !was_enabled && enabled && *len_timer > 0 {
+        *len_timer -= 1;
+        if *len_timer == 0 && !trigger {
+            keep = false;
+        }
+    }
+    if trigger && *len_timer == 0 {
+        *len_timer = max;
+        if extra_clock && enabled {
+            *len_timer -= 1;
+        }
+    }
+    keep
+}
+
+// the opened output device, the queue feeds its sink
+struct Output {
+    stream_handle: OutputStream,
+    sink: rodio::Sink,
+    queue: AudioQueue,
+}
+
 pub struct Sound {
     // registers
     apu_enabled: bool,
@@ -53,20 +205,60 @@ pub struct Sound {
     // counters
     frame_seq: u8,
     frame_counter: u32,
-
-    // sound stream
-    stream_handle: OutputStream,
-    sink: rodio::Sink,
+    // cycles since power on, timestamps the vgm log
+    cycles: u64,
+
+    // sound stream, None without an output device. The apu and the recorders still run
+    output: Option<Output>,
+    latency: AudioLatency,
+    // rate of the output device, the apu generates at it
+    sample_rate: u32,
+    // interleaved samples sent to the queue at once, half the target fill
+    buffer_size: usize,
+
+    // band limited synthesis of the left and right output, fed with amplitude changes
+    blip_left: Blip,
+    blip_right: Blip,
+    last_left: f32,
+    last_right: f32,
+
+    // output stage, a dc blocking high pass per side
+    model: AudioModel,
+    charge_factor: f32,
+    capacitor: [f32; 2],
+
+    mixer: Mixer,
+    // music player fade out, scales the master volume
+    fade: f32,
+    // lowest and highest level of each channel since the levels were last taken, for meters
+    level_range: [(f32, f32); 4],
 
     // sound buffer
-    buffer: Vec<f32>
+    buffer: Vec<f32>,
+
+    // fixed rate stereo mix for the avi recorder, None when not capturing
+    capture: Option<Capture>,
+    // fixed rate output for wav recording, None when not recording
+    wav_capture: Option<Capture>,
+    // register writes for vgm export, None when not logging
+    vgm_log: Option<VgmLog>,
+    // dac output history for the oscilloscope, None when not shown
+    scope: Option<Vec<[f32; 4]>>,
 }
 
 impl Sound {
     pub fn new() -> Self {
-        let stream_handle = rodio::OutputStreamBuilder::open_default_stream().unwrap();
-        let sink = rodio::Sink::connect_new(&stream_handle.mixer());
-        sink.set_volume(0.10);
+        let mixer = Mixer::new();
+        let latency = AudioLatency::Normal;
+        let output = match Self::open_output(latency, mixer.master) {
+            Ok(output) => Some(output),
+            Err(error) => {
+                println!("No audio output, running silent: {}", error);
+                None
+            }
+        };
+        let sample_rate = output.as_ref().map_or(FALLBACK_SAMPLE_RATE, |output| output.stream_handle.config().sample_rate());
+        let buffer_size = latency.target_fill(sample_rate) / 2;
         Self {
             apu_enabled: false,
             nr50: 0,
@@ -77,25 +269,243 @@ impl Sound {
             ch4: CH4::new(),
             frame_seq: 0,
             frame_counter: 0,
-            stream_handle,
-            sink,
-            buffer: vec![0.0; BUFFER_SIZE]
+            cycles: 0,
+            output,
+            latency,
+            sample_rate,
+            buffer_size,
+            blip_left: Blip::new(CPU_CLOCK as f64, sample_rate as f64),
+            blip_right: Blip::new(CPU_CLOCK as f64, sample_rate as f64),
+            last_left: 0.0,
+            last_right: 0.0,
+            model: AudioModel::Dmg,
+            charge_factor: AudioModel::Dmg.charge_factor(sample_rate as f64),
+            capacitor: [0.0; 2],
+            mixer,
+            fade: 1.0,
+            level_range: [(0.0, 0.0); 4],
+            buffer: Vec::with_capacity(buffer_size),
+            capture: None,
+            wav_capture: None,
+            vgm_log: None,
+            scope: None,
         }
     }
 
+    // Back to the power on state for a restarted machine. The output device, the mixer and model
+    // settings and any capture keep going
+    pub fn reset(&mut self) {
+        self.apu_enabled = false;
+        self.nr50 = 0;
+        self.nr51 = 0;
+        self.ch1 = CH1::new();
+        self.ch2 = CH2::new();
+        self.ch3 = CH3::new();
+        self.ch4 = CH4::new();
+        self.frame_seq = 0;
+        self.frame_counter = 0;
+        self.cycles = 0;
+        self.blip_left = Blip::new(CPU_CLOCK as f64, self.sample_rate as f64);
+        self.blip_right = Blip::new(CPU_CLOCK as f64, self.sample_rate as f64);
+        self.last_left = 0.0;
+        self.last_right = 0.0;
+        self.capacitor = [0.0; 2];
+        self.level_range = [(0.0, 0.0); 4];
+        self.buffer.clear();
+    }
+
+    // Opens the default device at its preferred sample rate, with the queue and device buffer sized for the latency
+    fn open_output(latency: AudioLatency, volume: f32) -> Result<Output, String> {
+        let stream_handle = stream::open_device(latency.millis() / 2)?;
+        let sample_rate = stream_handle.config().sample_rate();
+        let sink = rodio::Sink::connect_new(stream_handle.mixer());
+        sink.set_volume(volume);
+        let queue = AudioQueue::new(latency.target_fill(sample_rate));
+        sink.append(queue.source(sample_rate));
+        Ok(Output { stream_handle, sink, queue })
+    }
+
+    // Reopens the output device for a new latency, the device may pick a different sample rate
+    pub fn set_latency(&mut self, latency: AudioLatency) -> Result<(), String> {
+        if latency == self.latency {
+            return Ok(());
+        }
+        let output = Self::open_output(latency, self.mixer.master * self.fade)?;
+        self.sample_rate = output.stream_handle.config().sample_rate();
+        self.output = Some(output);
+        self.latency = latency;
+        self.buffer_size = latency.target_fill(self.sample_rate) / 2;
+        self.buffer.clear();
+        // fresh band limited buffers start from silence
+        self.blip_left = Blip::new(CPU_CLOCK as f64, self.sample_rate as f64);
+        self.blip_right = Blip::new(CPU_CLOCK as f64, self.sample_rate as f64);
+        self.last_left = 0.0;
+        self.last_right = 0.0;
+        self.charge_factor = self.model.charge_factor(self.sample_rate as f64);
+        Ok(())
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
     pub fn play_sound(&mut self, buffer: Vec<f32>) {
-        // wait for sink to clear
-        while self.sink.len() > 2 {
+        let Some(output) = &mut self.output else {
+            return;
+        };
+        // only wait when far ahead, normally the rate control keeps the queue near its target
+        while output.queue.len() > MAX_FILL_FACTOR * self.latency.target_fill(self.sample_rate) {
             std::thread::sleep(std::time::Duration::from_millis(1));
         }
+        output.queue.push(&buffer);
+
+        // nudge the output rate towards keeping the queue at its target
+        let rate = self.sample_rate as f64 * output.queue.rate_ratio();
+        self.blip_left.set_rates(CPU_CLOCK as f64, rate);
+        self.blip_right.set_rates(CPU_CLOCK as f64, rate);
+    }
+
+    // switches the output stage model, the capacitor keeps its charge
+    pub fn set_model(&mut self, model: AudioModel) {
+        self.model = model;
+        self.charge_factor = model.charge_factor(self.sample_rate as f64);
+        for capture in [&mut self.capture, &mut self.wav_capture].into_iter().flatten() {
+            capture.set_model(model);
+        }
+    }
+
+    // master volume goes to the device so recordings keep the full level
+    pub fn set_mixer(&mut self, mixer: Mixer) {
+        self.mixer = mixer;
+        if let Some(output) = &self.output {
+            output.sink.set_volume(mixer.master * self.fade);
+        }
+    }
+
+    pub fn set_fade(&mut self, gain: f32) {
+        self.fade = gain;
+        if let Some(output) = &self.output {
+            output.sink.set_volume(self.mixer.master * gain);
+        }
+    }
+
+    // Peak level of each channel after its gain since the last call, 0 to 1
+    pub fn take_levels(&mut self) -> [f32; 4] {
+        let levels = self.level_range.map(|(low, high)| ((high - low) / 2.0).min(1.0));
+        self.level_range = [(f32::MAX, f32::MIN); 4];
+        levels.map(|level| level.max(0.0))
+    }
+
+    // Removes the dc offset like the output capacitors, so dac on and off steps fade out instead of staying
+    fn high_pass(&mut self, side: usize, input: f32) -> f32 {
+        let output = input - self.capacitor[side];
+        self.capacitor[side] = input - output * self.charge_factor;
+        output
+    }
+
+    // starts or stops the fixed rate stereo capture for the avi recorder
+    pub fn set_capture(&mut self, enabled: bool) {
+        self.capture = if enabled {
+            Some(Capture::new(false, CPU_CLOCK as f64, self.model))
+        } else {
+            None
+        };
+    }
+
+    // takes the samples captured since the last call, interleaved left right
+    pub fn take_capture(&mut self) -> Vec<f32> {
+        match &mut self.capture {
+            Some(capture) => capture.take(),
+            None => Vec::new(),
+        }
+    }
+
+    // starts or stops the wav capture, stems are the four channel dac outputs instead of the stereo mix
+    pub fn set_wav_capture(&mut self, enabled: bool, stems: bool) {
+        self.wav_capture = if enabled {
+            Some(Capture::new(stems, CPU_CLOCK as f64, self.model))
+        } else {
+            None
+        };
+    }
+
+    // takes the wav samples finished since the last call, interleaved by channel
+    pub fn take_wav_capture(&mut self) -> Vec<f32> {
+        match &mut self.wav_capture {
+            Some(capture) => capture.take(),
+            None => Vec::new(),
+        }
+    }
+
+    // starts or stops keeping dac output history for the oscilloscope
+    pub fn set_scope(&mut self, enabled: bool) {
+        if enabled != self.scope.is_some() {
+            self.scope = if enabled { Some(Vec::new()) } else { None };
+        }
+    }
+
+    // takes the dac outputs of the four channels since the last call, one point every SCOPE_STEP cycles
+    pub fn take_scope(&mut self) -> Vec<[f32; 4]> {
+        match &mut self.scope {
+            Some(scope) => std::mem::take(scope),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn channel_status(&self) -> [ChannelStatus; 4] {
+        [self.ch1.status(), self.ch2.status(), self.ch3.status(), self.ch4.status()]
+    }
+
+    pub fn wave_ram(&self) -> [u8; 16] {
+        self.ch3.wave_ram
+    }
+
+    // Starts logging register writes, the log begins with the current register state so it plays on its own
+    pub fn start_vgm_log(&mut self) {
+        let mut log = VgmLog::new(self.cycles);
+        for (register, value) in self.register_snapshot() {
+            log.writes.push(VgmWrite { cycle: self.cycles, register, value });
+        }
+        self.vgm_log = Some(log);
+    }
+    // marks the current time as the loop start of the vgm log
+    pub fn mark_vgm_loop(&mut self) {
+        if let Some(log) = &mut self.vgm_log {
+            log.loop_mark = Some(self.cycles);
+        }
+    }
+    // stops logging and returns the log
+    pub fn take_vgm_log(&mut self) -> Option<VgmLog> {
+        let mut log = self.vgm_log.take()?;
+        log.end = self.cycles;
+        Some(log)
+    }
 
-        self.sink.append(SamplesBuffer::new(2, 48000, buffer));
+    // Writes that recreate the current register and wave ram state as (offset from ff10, value).
+    // Triggers are left out, channels start with the next trigger the game writes
+    fn register_snapshot(&self) -> Vec<(u8, u8)> {
+        let mut writes = vec![(0x16, (self.apu_enabled as u8) << 7)];
+        // wave ram is written with the ch3 dac off so every byte is reachable
+        writes.push((0x0a, 0));
+        for (n, byte) in self.ch3.wave_ram.iter().enumerate() {
+            writes.push((0x20 + n as u8, *byte));
+        }
+        let registers = [
+            (0x00, self.ch1.nr10), (0x01, self.ch1.nr11), (0x02, self.ch1.nr12), (0x03, self.ch1.nr13), (0x04, self.ch1.nr14 & 0x7f),
+            (0x06, self.ch2.nr21), (0x07, self.ch2.nr22), (0x08, self.ch2.nr23), (0x09, self.ch2.nr24 & 0x7f),
+            (0x0a, self.ch3.nr30), (0x0b, self.ch3.nr31), (0x0c, self.ch3.nr32), (0x0d, self.ch3.nr33), (0x0e, self.ch3.nr34 & 0x7f),
+            (0x10, self.ch4.nr41), (0x11, self.ch4.nr42), (0x12, self.ch4.nr43), (0x13, self.ch4.nr44 & 0x7f),
+            (0x14, self.nr50), (0x15, self.nr51),
+        ];
+        writes.extend_from_slice(&registers);
+        writes
     }
 
     pub fn tick(&mut self, cycles: u8) {
         if cycles == 0 {
             return;
         }
+        self.cycles += cycles as u64;
         let mut c_cycles = 0;
 
         // step through cycles 2 at a time
@@ -137,69 +547,45 @@ impl Sound {
                 }
             }
 
-            if (self.frame_counter % ((CPU_CLOCK / SAMPLE_RATE) as u32)) <= 1 {
-                let ch1_amp = self.ch1.get_amp();
-                let ch2_amp = self.ch2.get_amp();
-                let ch3_amp = self.ch3.get_amp();
-                let ch4_amp = self.ch4.get_amp();
-
-                let left_vol = ((self.nr50 >> 4) & 0b111) as f32 / 7.0;
-                let right_vol = (self.nr50 & 0b111) as f32 / 7.0;
-
-                let ch1_left = (self.nr51 & 0x10) != 0;
-                let ch2_left = (self.nr51 & 0x20) != 0;
-                let ch3_left = (self.nr51 & 0x40) != 0;
-                let ch4_left = (self.nr51 & 0x80) != 0;
-
-                let ch1_right = (self.nr51 & 0x01) != 0;
-                let ch2_right = (self.nr51 & 0x02) != 0;
-                let ch3_right = (self.nr51 & 0x04) != 0;
-                let ch4_right = (self.nr51 & 0x08) != 0;
-
-                let mut left: f32 = 0.0;
-                let mut right: f32 = 0.0;
-
-                if self.ch1.enable {
-                    if ch1_left {
-                        left += ch1_amp;
-                    }
-                    if ch1_right {
-                        right += ch1_amp;
-                    }
-                }
-                if self.ch2.enable {
-                    if ch2_left {
-                        left += ch2_amp;
-                    }
-                    if ch2_right {
-                        right += ch2_amp;
-                    }
-                }
-                if self.ch3.enable {
-                    if ch3_left {
-                        left += ch3_amp;
-                    }
-                    if ch3_right {
-                        right += ch3_amp;
-                    }
-                }
-                if self.ch4.enable {
-                    if ch4_left {
-                        left += ch4_amp;
-                    }
-                    if ch4_right {
-                        right += ch4_amp;
-                    }
+            // amplitude changes go into the band limited buffers at their exact time
+            let channels = [
+                self.ch1.get_amp(),
+                self.ch2.get_amp(),
+                self.ch3.get_amp(),
+                self.ch4.get_amp(),
+            ];
+            if let (true, Some(scope)) = (self.frame_counter % SCOPE_STEP == 0, &mut self.scope) {
+                scope.push(channels);
+            }
+            let (left, right) = self.mix(channels);
+            if let Some(capture) = &mut self.capture {
+                capture.add(channels, (left, right), 2);
+            }
+            if let Some(capture) = &mut self.wav_capture {
+                capture.add(channels, (left, right), 2);
+            }
+            self.blip_left.add_delta(left - self.last_left);
+            self.blip_right.add_delta(right - self.last_right);
+            self.last_left = left;
+            self.last_right = right;
+            self.blip_left.advance(2);
+            self.blip_right.advance(2);
+
+            // interleave the finished samples
+            let avail = self.blip_left.samples_avail();
+            if avail > 0 {
+                let left = self.blip_left.read_samples(avail);
+                let right = self.blip_right.read_samples(avail);
+                for (l, r) in left.into_iter().zip(right) {
+                    let l = self.high_pass(0, l);
+                    let r = self.high_pass(1, r);
+                    self.buffer.push(l);
+                    self.buffer.push(r);
                 }
-
-                left /= 4.0;
-                right /= 4.0;
-                self.buffer.push(left * left_vol);
-                self.buffer.push(right * right_vol);
             }
 
             // If sound buffer is full, send it
-            if self.buffer.len() >= BUFFER_SIZE {
+            if self.buffer.len() >= self.buffer_size {
                 self.play_sound(self.buffer.clone());
                 self.buffer.clear();
             }
@@ -208,6 +594,37 @@ impl Sound {
         }
     }
 
+    // Current left and right output level, dac outputs scaled by the mixer, panned by nr51 and scaled by nr50
+    fn mix(&mut self, channels: [f32; 4]) -> (f32, f32) {
+        let mut left: f32 = 0.0;
+        let mut right: f32 = 0.0;
+        for (n, amp) in channels.into_iter().enumerate() {
+            let amp = amp * self.mixer.channel_gain(n);
+            let (low, high) = &mut self.level_range[n];
+            *low = low.min(amp);
+            *high = high.max(amp);
+            if self.nr51 & (0x10 << n) != 0 {
+                left += amp;
+            }
+            if self.nr51 & (0x01 << n) != 0 {
+                right += amp;
+            }
+        }
+
+        // the nr50 vin bits (7 and 3) would mix in the cartridge vin pin, but no supported cartridge drives
+        // it, so they are stored for reads and add nothing
+
+        // master volume 0 is still audible, the levels are 1/8 to 8/8
+        let left_vol = (((self.nr50 >> 4) & 0b111) + 1) as f32 / 8.0;
+        let right_vol = ((self.nr50 & 0b111) + 1) as f32 / 8.0;
+        (left / 4.0 * left_vol, right / 4.0 * right_vol)
+    }
+
+    // true when the next frame sequencer step won't clock the length counters
+    fn len_extra_clock(&self) -> bool {
+        self.frame_seq % 2 == 0
+    }
+
     // $ff10 - $ff26 audio registers
     // $ff30 - $ff3f wave ram
     pub fn get(&self, address: u16) -> u8 {
@@ -241,8 +658,13 @@ impl Sound {
                     | ((self.ch4.enable as u8) << 3)
             }
             0xff30..=0xff3f => {
+                // while playing, DMG only reaches the byte ch3 is reading, and only right as it reads it
                 if self.ch3.enable {
-                    self.ch3.wave_ram[self.ch3.wave_duty_pos as usize / 2]
+                    if self.ch3.read_ago < 2 {
+                        self.ch3.wave_ram[self.ch3.wave_duty_pos as usize / 2]
+                    } else {
+                        0xff
+                    }
                 } else {
                     self.ch3.wave_ram[(address - 0xff30) as usize]
                 }
@@ -253,7 +675,16 @@ impl Sound {
         }
     }
 
+    // $ff10 - $ff26 audio registers
+    // $ff30 - $ff3f wave ram
     pub fn set(&mut self, address: u16, value: u8) {
+        if let Some(log) = &mut self.vgm_log {
+            log.writes.push(VgmWrite { cycle: self.cycles, register: (address - 0xff10) as u8, value });
+        }
+        self.write_register(address, value);
+    }
+
+    fn write_register(&mut self, address: u16, value: u8) {
         // println!("writing sound at address {:04x}", address);
         // If apu is not enabled and not in the writable addresses when off, return
         if !(self.apu_enabled
@@ -272,10 +703,16 @@ impl Sound {
 
         match address {
             // Channel 1
-            0xff10 => self.ch1.nr10 = value,
+            0xff10 => {
+                // leaving negate mode after a negated calculation since the trigger disables the channel
+                if self.ch1.sweep_negated && value & 0b1000 == 0 {
+                    self.ch1.enable = false;
+                }
+                self.ch1.nr10 = value;
+            }
             0xff11 => {
                 self.ch1.nr11 = value;
-                self.ch1.len_timer = 64 - (value & 0x3f);
+                self.ch1.len_timer = 64 - (value & 0x3f) as u16;
             }
             0xff12 => {
                 self.ch1.nr12 = value;
@@ -287,6 +724,10 @@ impl Sound {
             0xff13 => self.ch1.nr13 = value,
             0xff14 => {
                 let trigger = value & 0x80 != 0;
+                let extra_clock = self.len_extra_clock();
+                if !write_len_enable(&mut self.ch1.len_timer, 64, self.ch1.nr14, value, extra_clock) {
+                    self.ch1.enable = false;
+                }
                 self.ch1.nr14 = value;
                 
                 // on trigger
@@ -296,11 +737,6 @@ impl Sound {
                         self.ch1.enable = true;
                     }
 
-                    // if length timer is expired, reset
-                    if self.ch1.len_timer == 0 {
-                        self.ch1.len_timer = 64;
-                    }
-
                     // set freq timer
                     let freq = u16::from_be_bytes([self.ch1.nr14, self.ch1.nr13]) & 0x7ff;
                     self.ch1.freq_timer = (2048 - freq) * 4;
@@ -313,6 +749,7 @@ impl Sound {
                     self.ch1.cur_vol = self.ch1.nr12 >> 4;
 
                     // reset sweep
+                    self.ch1.sweep_negated = false;
                     let sweep_period = (self.ch1.nr10 >> 4) & 0b111;
                     let sweep_shift = self.ch1.nr10 & 0b111;
                     self.ch1.shadow_freq = freq;
@@ -331,7 +768,7 @@ impl Sound {
             // Channel 2
             0xff16 => {
                 self.ch2.nr21 = value;
-                self.ch2.len_timer = 64 - (value & 0x3f);
+                self.ch2.len_timer = 64 - (value & 0x3f) as u16;
             }
             0xff17 => {
                 self.ch2.nr22 = value;
@@ -343,6 +780,10 @@ impl Sound {
             0xff18 => self.ch2.nr23 = value,
             0xff19 => {
                 let trigger = value & 0x80 != 0;
+                let extra_clock = self.len_extra_clock();
+                if !write_len_enable(&mut self.ch2.len_timer, 64, self.ch2.nr24, value, extra_clock) {
+                    self.ch2.enable = false;
+                }
                 self.ch2.nr24 = value;
                 
                 // on trigger
@@ -352,11 +793,6 @@ impl Sound {
                         self.ch2.enable = true;
                     }
 
-                    // if length timer is expired, reset
-                    if self.ch2.len_timer == 0 {
-                        self.ch2.len_timer = 64;
-                    }
-
                     // set freq timer
                     let freq = u16::from_be_bytes([self.ch2.nr24, self.ch2.nr23]) & 0x7ff;
                     self.ch2.freq_timer = (2048 - freq) * 4;
@@ -384,23 +820,27 @@ impl Sound {
             0xff1d => self.ch3.nr33 = value,
             0xff1e => {
                 let trigger = value & 0x80 != 0;
+                let extra_clock = self.len_extra_clock();
+                if !write_len_enable(&mut self.ch3.len_timer, 256, self.ch3.nr34, value, extra_clock) {
+                    self.ch3.enable = false;
+                }
                 self.ch3.nr34 = value;
                 
                 // on trigger
                 if trigger {
+                    // DMG bug, retriggering while the channel is about to read a sample corrupts wave ram
+                    if self.ch3.enable && self.ch3.freq_timer <= 2 {
+                        self.ch3.corrupt_wave_ram();
+                    }
+
                     // enable channel
                     if self.ch3.nr30 & 0x80 != 0 {
                         self.ch3.enable = true;
                     }
 
-                    // if length timer is expired, reset
-                    if self.ch3.len_timer == 0 {
-                        self.ch3.len_timer = 256;
-                    }
-
-                    // set freq timer
+                    // set freq timer, the first sample is fetched after an extra delay
                     let freq = u16::from_be_bytes([self.ch3.nr34, self.ch3.nr33]) & 0x7ff;
-                    self.ch3.freq_timer = (2048 - freq) * 2;
+                    self.ch3.freq_timer = (2048 - freq) * 2 + 6;
                     
                     // set wave duty position
                     self.ch3.wave_duty_pos = 0;
@@ -409,7 +849,7 @@ impl Sound {
             // Channel 4
             0xff20 => {
                 self.ch4.nr41 = value;
-                self.ch4.len_timer = 64 - (value & 0x3f);
+                self.ch4.len_timer = 64 - (value & 0x3f) as u16;
             }
             0xff21 => {
                 self.ch4.nr42 = value;
@@ -421,6 +861,10 @@ impl Sound {
             0xff22 => self.ch4.nr43 = value,
             0xff23 => {
                 let trigger = value & 0x80 != 0;
+                let extra_clock = self.len_extra_clock();
+                if !write_len_enable(&mut self.ch4.len_timer, 64, self.ch4.nr44, value, extra_clock) {
+                    self.ch4.enable = false;
+                }
                 self.ch4.nr44 = value;
                 
                 // on trigger
@@ -433,12 +877,6 @@ impl Sound {
                     let divisor = CH4_DIV_TABLE[(self.ch4.nr43 & 0b111) as usize];
                     let shift = self.ch4.nr43 >> 4;
                     self.ch4.freq_timer = (divisor as u16) << shift;
-                    
-                    // if length timer is expired, reset
-                    if self.ch4.len_timer == 0 {
-                        // if length is now enabled, sub extra frame seq clock
-                        self.ch4.len_timer = 64;
-                    }
 
                     // reset env
                     self.ch4.env_timer = self.ch4.nr42 & 0b111;
@@ -455,20 +893,34 @@ impl Sound {
                 
                 // turning sound on to off
                 if self.apu_enabled && !enabled {
-                    // reset sound registers
+                    // reset sound registers, length counters survive power off on DMG
+                    let len_timers = [self.ch1.len_timer, self.ch2.len_timer, self.ch3.len_timer, self.ch4.len_timer];
                     for addr in 0xff10..=0xff25 {
-                        self.set(addr, 0);
+                        self.write_register(addr, 0);
                     }
+                    [self.ch1.len_timer, self.ch2.len_timer, self.ch3.len_timer, self.ch4.len_timer] = len_timers;
                     self.apu_enabled = false;
                 } 
                     
                 // turning sound off to on
                 else if !self.apu_enabled && enabled {
                     self.apu_enabled = true;
+                    // frame sequencer restarts so the next step is 0, duty steps restart too
+                    self.frame_seq = 7;
+                    self.ch1.wave_duty_pos = 0;
+                    self.ch2.wave_duty_pos = 0;
+                }
+            }
+            // Wave RAM, same access rules as reads while ch3 plays
+            0xff30..=0xff3f => {
+                if self.ch3.enable {
+                    if self.ch3.read_ago < 2 {
+                        self.ch3.wave_ram[self.ch3.wave_duty_pos as usize / 2] = value;
+                    }
+                } else {
+                    self.ch3.wave_ram[(address - 0xff30) as usize] = value;
                 }
             }
-            // Wave RAM
-            0xff30..=0xff3f => self.ch3.wave_ram[(address - 0xff30) as usize] = value,
             _ => {}
         }
     }
@@ -485,7 +937,7 @@ struct CH1 {
 
     // none accessible registers
     enable: bool,
-    len_timer: u8,
+    len_timer: u16,
     freq_timer: u16,
     env_timer: u8,
     wave_duty_pos: u8,
@@ -495,6 +947,8 @@ struct CH1 {
     sweep_enable: bool,
     sweep_timer: u8,
     shadow_freq: u16,
+    // a calculation used negate mode since the last trigger
+    sweep_negated: bool,
 }
 
 impl CH1 {
@@ -514,6 +968,7 @@ impl CH1 {
             sweep_enable: false,
             sweep_timer: 0,
             shadow_freq: 0,
+            sweep_negated: false,
         }
     }
     pub fn tick(&mut self, cycles: u8) {
@@ -592,6 +1047,7 @@ impl CH1 {
         // decrementing
         if sweep_dir {
             new_freq = self.shadow_freq - new_freq;
+            self.sweep_negated = true;
         }
         // incrementing
         else {
@@ -605,14 +1061,30 @@ impl CH1 {
         new_freq
     }
 
+    pub fn status(&self) -> ChannelStatus {
+        let freq = u16::from_be_bytes([self.nr14, self.nr13]) & 0x7ff;
+        ChannelStatus {
+            enabled: self.enable,
+            dac: self.nr12 & 0xf8 != 0,
+            frequency: 131072.0 / (2048 - freq) as f32,
+            shape: DUTY_NAMES[(self.nr11 >> 6) as usize],
+            volume: self.cur_vol,
+            length: (self.nr14 & 0x40 != 0).then_some(self.len_timer),
+        }
+    }
+
+    // Dac output, a dac that is on but has a disabled channel outputs a steady level
     pub fn get_amp(&self) -> f32 {
-        if self.enable {
+        if self.nr12 & 0xF8 == 0 {
+            return 0.0;
+        }
+        let input = if self.enable {
             let duty = (self.nr11 >> 6) & 0b11;
-            let input = (get_wave_duty(duty, self.wave_duty_pos) * self.cur_vol) as f32;
-            (input / 7.5) - 1.0
+            (get_wave_duty(duty, self.wave_duty_pos) * self.cur_vol) as f32
         } else {
             0.0
-        }
+        };
+        (input / 7.5) - 1.0
     }
 }
 
@@ -626,7 +1098,7 @@ struct CH2 {
 
     // none accessible registers
     enable: bool,
-    len_timer: u8,
+    len_timer: u16,
     freq_timer: u16,
     env_timer: u8,
     wave_duty_pos: u8,
@@ -685,15 +1157,31 @@ impl CH2 {
         self.freq_timer = freq_timer_signed as u16;
     }
 
+    pub fn status(&self) -> ChannelStatus {
+        let freq = u16::from_be_bytes([self.nr24, self.nr23]) & 0x7ff;
+        ChannelStatus {
+            enabled: self.enable,
+            dac: self.nr22 & 0xf8 != 0,
+            frequency: 131072.0 / (2048 - freq) as f32,
+            shape: DUTY_NAMES[(self.nr21 >> 6) as usize],
+            volume: self.cur_vol,
+            length: (self.nr24 & 0x40 != 0).then_some(self.len_timer),
+        }
+    }
+
     // gets the current amp from the channel
+    // Dac output, same as ch1
     pub fn get_amp(&self) -> f32 {
-        if self.enable {
+        if self.nr22 & 0xF8 == 0 {
+            return 0.0;
+        }
+        let input = if self.enable {
             let duty = (self.nr21 >> 6) & 0b11;
-            let input = (get_wave_duty(duty, self.wave_duty_pos) * self.cur_vol) as f32;
-            (input / 7.5) - 1.0
+            (get_wave_duty(duty, self.wave_duty_pos) * self.cur_vol) as f32
         } else {
             0.0
-        }
+        };
+        (input / 7.5) - 1.0
     }
 }
 
@@ -716,6 +1204,8 @@ struct CH3 {
 
     // selects wave ram index
     wave_duty_pos: u8,
+    // cycles since the last wave ram sample fetch
+    read_ago: u16,
 }
 
 impl CH3 {
@@ -731,6 +1221,7 @@ impl CH3 {
             len_timer: 0,
             freq_timer: 0,
             wave_duty_pos: 0,
+            read_ago: u16::MAX,
         }
     }
     pub fn tick_len(&mut self) {
@@ -748,10 +1239,13 @@ impl CH3 {
         // freq timer max value should be 4096? About 14 bits so signed should be safe
         let mut freq_timer_signed = self.freq_timer as i16;
         freq_timer_signed -= cycles as i16;
+        self.read_ago = self.read_ago.saturating_add(cycles as u16);
 
         while freq_timer_signed <= 0 {
             // Frequency is lower 3 bits of nr24 and 8 bits of nr23 combined ( max value is 2047 )
             let freq = u16::from_be_bytes([self.nr34, self.nr33]) & 0x7ff;
+            // the sample was fetched when the timer hit 0
+            self.read_ago = (-freq_timer_signed) as u16;
             // keep overflow
             freq_timer_signed += ((2048 - freq) * 2) as i16;
 
@@ -762,16 +1256,47 @@ impl CH3 {
         self.freq_timer = freq_timer_signed as u16;
     }
 
+    // Retrigger corruption, the first bytes of wave ram get overwritten by the block being read
+    fn corrupt_wave_ram(&mut self) {
+        let position = ((self.wave_duty_pos + 1) % 32) as usize / 2;
+        if position < 4 {
+            self.wave_ram[0] = self.wave_ram[position];
+        } else {
+            let block = position & !0b11;
+            self.wave_ram.copy_within(block..block + 4, 0);
+        }
+    }
+
+    // the volume is the loudest sample the output level lets through
+    pub fn status(&self) -> ChannelStatus {
+        let freq = u16::from_be_bytes([self.nr34, self.nr33]) & 0x7ff;
+        let level = (self.nr32 >> 5) & 0b11;
+        ChannelStatus {
+            enabled: self.enable,
+            dac: self.nr30 & 0x80 != 0,
+            // 32 samples per period
+            frequency: 65536.0 / (2048 - freq) as f32,
+            shape: ["mute", "100%", "50%", "25%"][level as usize],
+            volume: 0xf >> CH3_SHIFT_TABLE[level as usize],
+            length: (self.nr34 & 0x40 != 0).then_some(self.len_timer),
+        }
+    }
+
+    // Dac output, same as ch1
     pub fn get_amp(&self) -> f32 {
-        if self.enable {
+        if self.nr30 & 0x80 == 0 {
+            return 0.0;
+        }
+        let input = if self.enable {
             let vol_shift = CH3_SHIFT_TABLE[((self.nr32 >> 5) & 0b11) as usize];
             let mut sample =
                 self.wave_ram[self.wave_duty_pos as usize / 2] >> ((self.wave_duty_pos % 2) * 4);
             sample &= 0xf;
-            ((sample >> vol_shift) as f32 / 7.5) - 1.0
+            (sample >> vol_shift) as f32
         } else {
             0.0
-        }
+        };
+        (input / 7.5) - 1.0
     }
 }
 
@@ -785,7 +1310,7 @@ struct CH4 {
 
     // none accessible registers
     enable: bool,
-    len_timer: u8,
+    len_timer: u16,
     freq_timer: u16,
     cur_vol: u8,
     env_timer: u8,
@@ -844,12 +1369,29 @@ impl CH4 {
         run_env(&mut self.env_timer, &mut self.cur_vol, self.nr42);
     }
 
+    pub fn status(&self) -> ChannelStatus {
+        let divisor = CH4_DIV_TABLE[(self.nr43 & 0b111) as usize] as u32;
+        let shift = self.nr43 >> 4;
+        ChannelStatus {
+            enabled: self.enable,
+            dac: self.nr42 & 0xf8 != 0,
+            frequency: CPU_CLOCK as f32 / (divisor << shift) as f32,
+            shape: if self.nr43 & 0b1000 != 0 { "7 bit" } else { "15 bit" },
+            volume: self.cur_vol,
+            length: (self.nr44 & 0x40 != 0).then_some(self.len_timer),
+        }
+    }
+
+    // Dac output, same as ch1
     pub fn get_amp(&self) -> f32 {
-        if self.enable {
-            let input = (!self.lfsr & 0b1) as f32 * self.cur_vol as f32;
-            (input / 7.5) - 1.0
+        if self.nr42 & 0xF8 == 0 {
+            return 0.0;
+        }
+        let input = if self.enable {
+            (!self.lfsr & 0b1) as f32 * self.cur_vol as f32
         } else {
             0.0
-        }
+        };
+        (input / 7.5) - 1.0
     }
 }
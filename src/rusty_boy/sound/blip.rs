@@ -0,0 +1,111 @@
+// Band limited synthesis in the style of blip_buf. Amplitude changes are added as deltas at
+// their exact clock time and spread over a few output samples with a windowed sinc kernel,
+// so high square and noise frequencies don't alias and the clock to sample ratio can be anything
+
+// kernel taps per delta and sub sample phases the kernel is tabulated for
+const KERNEL_WIDTH: usize = 16;
+const PHASES: usize = 64;
+// time is kept in output samples with this many fractional bits
+const FRAC_BITS: u32 = 32;
+// lowpass cutoff relative to the output sample rate, just under nyquist
+const CUTOFF: f64 = 0.45;
+
+pub struct Blip {
+    // output samples per clock, fixed point
+    factor: u64,
+    // current time in output samples, fixed point
+    offset: u64,
+    // pending deltas, summed into samples when read
+    buffer: Vec<f32>,
+    integrator: f32,
+    kernel: Vec<[f32; KERNEL_WIDTH]>,
+}
+
+impl Blip {
+    pub fn new(clock_rate: f64, sample_rate: f64) -> Self {
+        let mut blip = Self {
+            factor: 0,
+            offset: 0,
+            buffer: vec![0.0; KERNEL_WIDTH],
+            integrator: 0.0,
+            kernel: make_kernel(),
+        };
+        blip.set_rates(clock_rate, sample_rate);
+        blip
+    }
+
+    // Rates can be changed at any time, deltas already added keep their place
+    pub fn set_rates(&mut self, clock_rate: f64, sample_rate: f64) {
+        self.factor = (sample_rate / clock_rate * (1u64 << FRAC_BITS) as f64) as u64;
+    }
+
+    // Adds an amplitude change at the current time
+    pub fn add_delta(&mut self, delta: f32) {
+        if delta == 0.0 {
+            return;
+        }
+        let index = (self.offset >> FRAC_BITS) as usize;
+        let phase = ((self.offset >> (FRAC_BITS - PHASES.trailing_zeros())) as usize) & (PHASES - 1);
+        if self.buffer.len() < index + KERNEL_WIDTH {
+            self.buffer.resize(index + KERNEL_WIDTH, 0.0);
+        }
+        for (sample, tap) in self.buffer[index..index + KERNEL_WIDTH].iter_mut().zip(self.kernel[phase].iter()) {
+            *sample += delta * tap;
+        }
+    }
+
+    // Moves time forward
+    pub fn advance(&mut self, clocks: u32) {
+        self.offset += clocks as u64 * self.factor;
+    }
+
+    // Output samples that are complete and can be read
+    pub fn samples_avail(&self) -> usize {
+        (self.offset >> FRAC_BITS) as usize
+    }
+
+    // Reads up to count finished samples
+    pub fn read_samples(&mut self, count: usize) -> Vec<f32> {
+        let count = count.min(self.samples_avail());
+        let mut samples = Vec::with_capacity(count);
+        for delta in self.buffer.drain(..count) {
+            self.integrator += delta;
+            samples.push(self.integrator);
+        }
+        if self.buffer.len() < KERNEL_WIDTH {
+            self.buffer.resize(KERNEL_WIDTH, 0.0);
+        }
+        self.offset -= (count as u64) << FRAC_BITS;
+        samples
+    }
+}
+
+// Windowed sinc impulse per phase, each normalized so a delta adds up to exactly its size
+fn make_kernel() -> Vec<[f32; KERNEL_WIDTH]> {
+    (0..PHASES)
+        .map(|phase| {
+            let fraction = phase as f64 / PHASES as f64;
+            let mut taps = [0.0f64; KERNEL_WIDTH];
+            for (i, tap) in taps.iter_mut().enumerate() {
+                // distance from the delta, centered in the kernel
+                let x = i as f64 - (KERNEL_WIDTH / 2) as f64 + 1.0 - fraction;
+                let sinc = if x == 0.0 {
+                    1.0
+                } else {
+                    let t = std::f64::consts::PI * 2.0 * CUTOFF * x;
+                    t.sin() / t
+                };
+                // blackman window over the kernel width
+                let w = std::f64::consts::PI * 2.0 * (x / KERNEL_WIDTH as f64 + 0.5);
+                let window = 0.42 - 0.5 * w.cos() + 0.08 * (2.0 * w).cos();
+                *tap = sinc * window.max(0.0);
+            }
+            let sum: f64 = taps.iter().sum();
+            let mut kernel = [0.0f32; KERNEL_WIDTH];
+            for (k, tap) in kernel.iter_mut().zip(taps.iter()) {
+                *k = (tap / sum) as f32;
+            }
+            kernel
+        })
+        .collect()
+}
@@ -0,0 +1,68 @@
+// Fixed rate copy of the apu output for the recorders. It has its own band limited buffers at
+// RECORD_SAMPLE_RATE, so neither the live device rate nor its rate control ever reach a recording
+use super::blip::Blip;
+use super::AudioModel;
+
+// sample rate of every recording, whatever the output device runs at
+pub const RECORD_SAMPLE_RATE: u32 = 48000;
+
+pub struct Capture {
+    // true for the four channel dac outputs, false for the stereo mix
+    stems: bool,
+    blips: Vec<Blip>,
+    last: Vec<f32>,
+    capacitors: Vec<f32>,
+    // dc blocking of the model at RECORD_SAMPLE_RATE
+    charge_factor: f32,
+    // finished samples, interleaved by channel
+    samples: Vec<f32>,
+}
+
+impl Capture {
+    pub fn new(stems: bool, clock_rate: f64, model: AudioModel) -> Self {
+        let channels = if stems { 4 } else { 2 };
+        Self {
+            stems,
+            blips: (0..channels).map(|_| Blip::new(clock_rate, RECORD_SAMPLE_RATE as f64)).collect(),
+            last: vec![0.0; channels],
+            capacitors: vec![0.0; channels],
+            charge_factor: model.charge_factor(RECORD_SAMPLE_RATE as f64),
+            samples: Vec::new(),
+        }
+    }
+
+    // switches the dc blocking to another model, the capacitors keep their charge
+    pub fn set_model(&mut self, model: AudioModel) {
+        self.charge_factor = model.charge_factor(RECORD_SAMPLE_RATE as f64);
+    }
+
+    // Adds the levels of one step of cycles, the channel levels are taken before nr51 and the mixer
+    pub fn add(&mut self, channels: [f32; 4], mix: (f32, f32), cycles: u32) {
+        let levels = if self.stems { channels.to_vec() } else { vec![mix.0, mix.1] };
+        for ((blip, last), level) in self.blips.iter_mut().zip(self.last.iter_mut()).zip(levels) {
+            blip.add_delta(level - *last);
+            *last = level;
+            blip.advance(cycles);
+        }
+
+        let avail = self.blips[0].samples_avail();
+        if avail == 0 {
+            return;
+        }
+        let outputs: Vec<Vec<f32>> = self.blips.iter_mut().map(|blip| blip.read_samples(avail)).collect();
+        for i in 0..avail {
+            for (output, capacitor) in outputs.iter().zip(self.capacitors.iter_mut()) {
+                // same dc blocking as the live output
+                let input = output[i];
+                let filtered = input - *capacitor;
+                *capacitor = input - filtered * self.charge_factor;
+                self.samples.push(filtered);
+            }
+        }
+    }
+
+    // takes the finished samples
+    pub fn take(&mut self) -> Vec<f32> {
+        std::mem::take(&mut self.samples)
+    }
+}
@@ -0,0 +1,116 @@
+// Sample queue between the emulator and the output device. The device pulls from it at its
+// own pace, and the fill level steers the emulated output rate so the queue neither runs dry nor grows
+use rodio::cpal::traits::{DeviceTrait, HostTrait};
+use rodio::cpal::{self, BufferSize, SupportedBufferSize};
+use rodio::source::Source;
+use rodio::{OutputStream, OutputStreamBuilder};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// largest speed change applied to the audio, small enough to not be heard as pitch
+const MAX_RATE_DELTA: f64 = 0.005;
+// weight of a new fill reading in the running average
+const FILL_SMOOTHING: f64 = 0.05;
+
+// Opens the default output device in its preferred format with a buffer of about buffer_ms.
+// Falls back to the device's own buffer size when it refuses the requested one
+pub fn open_device(buffer_ms: u32) -> Result<OutputStream, String> {
+    let device = cpal::default_host().default_output_device().ok_or("No audio output device")?;
+    let config = device.default_output_config().map_err(|e| e.to_string())?;
+    let mut frames = config.sample_rate().0 * buffer_ms / 1000;
+    if let SupportedBufferSize::Range { min, max } = config.buffer_size() {
+        frames = frames.max(*min).min(*max);
+    }
+    OutputStreamBuilder::from_device(device)
+        .and_then(|builder| builder.with_buffer_size(BufferSize::Fixed(frames)).open_stream())
+        .or_else(|_| OutputStreamBuilder::open_default_stream())
+        .map_err(|e| e.to_string())
+}
+
+pub struct AudioQueue {
+    samples: Arc<Mutex<VecDeque<f32>>>,
+    // interleaved samples the queue is kept at
+    target: usize,
+    fill_average: f64,
+}
+
+impl AudioQueue {
+    pub fn new(target: usize) -> Self {
+        Self {
+            samples: Arc::new(Mutex::new(VecDeque::with_capacity(target * 4))),
+            target,
+            fill_average: target as f64,
+        }
+    }
+
+    // Source for the output device, outputs silence when the queue runs dry
+    pub fn source(&self, sample_rate: u32) -> QueueSource {
+        QueueSource {
+            samples: self.samples.clone(),
+            sample_rate,
+            right: None,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.lock().unwrap().len()
+    }
+
+    pub fn push(&mut self, buffer: &[f32]) {
+        let fill = {
+            let mut samples = self.samples.lock().unwrap();
+            samples.extend(buffer.iter().copied());
+            samples.len()
+        };
+        self.fill_average += (fill as f64 - self.fill_average) * FILL_SMOOTHING;
+    }
+
+    // Output rate multiplier, above 1 while the queue is below target so it fills up, below 1 while above
+    pub fn rate_ratio(&self) -> f64 {
+        let error = (self.target as f64 - self.fill_average) / self.target as f64;
+        1.0 + error.clamp(-1.0, 1.0) * MAX_RATE_DELTA
+    }
+}
+
+pub struct QueueSource {
+    samples: Arc<Mutex<VecDeque<f32>>>,
+    sample_rate: u32,
+    // right half of the frame being played
+    right: Option<f32>,
+}
+
+impl Iterator for QueueSource {
+    type Item = f32;
+
+    // Takes whole frames so an underrun can't swap the left and right channels
+    fn next(&mut self) -> Option<f32> {
+        if let Some(right) = self.right.take() {
+            return Some(right);
+        }
+        let mut samples = self.samples.lock().unwrap();
+        if samples.len() < 2 {
+            self.right = Some(0.0);
+            return Some(0.0);
+        }
+        self.right = samples.get(1).copied();
+        let left = samples.pop_front();
+        samples.pop_front();
+        left
+    }
+}
+
+impl Source for QueueSource {
+    fn current_span_len(&self) -> Option<usize> {
+        None
+    }
+    fn channels(&self) -> u16 {
+        2
+    }
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
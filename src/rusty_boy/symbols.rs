@@ -0,0 +1,74 @@
+// RGBDS .sym symbol files, one "bank:address label" per line
+use crate::rusty_boy::memory::region_name;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+pub struct Symbols {
+    // label by (bank, address)
+    labels: BTreeMap<(u16, u16), String>,
+    // (bank, address) by label
+    addresses: HashMap<String, (u16, u16)>,
+}
+
+impl Symbols {
+    pub fn new() -> Self {
+        Self {
+            labels: BTreeMap::new(),
+            addresses: HashMap::new(),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        Ok(Self::parse(&text))
+    }
+
+    // Lines that aren't "bank:address label" are skipped, comments start with ;
+    pub fn parse(text: &str) -> Self {
+        let mut symbols = Self::new();
+        for line in text.lines() {
+            let line = line.split(';').next().unwrap_or("");
+            let mut parts = line.split_whitespace();
+            let (Some(location), Some(name)) = (parts.next(), parts.next()) else {
+                continue;
+            };
+            let Some((bank, address)) = location.split_once(':') else {
+                continue;
+            };
+            let (Ok(bank), Ok(address)) = (u16::from_str_radix(bank, 16), u16::from_str_radix(address, 16)) else {
+                continue;
+            };
+            // the first label at an address wins, later ones are usually local aliases
+            symbols.labels.entry((bank, address)).or_insert_with(|| name.to_string());
+            symbols.addresses.insert(name.to_string(), (bank, address));
+        }
+        symbols
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+
+    // Label exactly at an address in the given bank
+    pub fn label(&self, bank: u16, address: u16) -> Option<&str> {
+        self.labels.get(&(bank, address)).map(|name| name.as_str())
+    }
+
+    // Closest label at or before an address in the same bank and region, as "label+offset"
+    pub fn describe(&self, bank: u16, address: u16) -> Option<String> {
+        let ((_, start), name) = self.labels.range((bank, 0)..=(bank, address)).next_back()?;
+        if region_name(*start) != region_name(address) {
+            return None;
+        }
+        match address - start {
+            0 => Some(name.clone()),
+            offset => Some(format!("{}+{}", name, offset)),
+        }
+    }
+
+    // Bank and address of a label
+    pub fn lookup(&self, name: &str) -> Option<(u16, u16)> {
+        self.addresses.get(name).copied()
+    }
+}
@@ -0,0 +1,100 @@
+mod common;
+
+use rustyboy::rusty_boy::{MemoryView, RustyBoy};
+
+const NR11: u16 = 0xff11;
+const NR12: u16 = 0xff12;
+const NR14: u16 = 0xff14;
+const NR52: u16 = 0xff26;
+
+// bits that always read back as 1, $FF10 to $FF2F as in blargg's dmg_sound 01-registers
+const READ_MASKS: [u8; 0x20] = [
+    0x80, 0x3f, 0x00, 0xff, 0xbf, // NR10-NR14
+    0xff, 0x3f, 0x00, 0xff, 0xbf, // NR20-NR24
+    0x7f, 0xff, 0x9f, 0xff, 0xbf, // NR30-NR34
+    0xff, 0xff, 0x00, 0x00, 0xbf, // NR40-NR44
+    0x00, 0x00, 0x70, // NR50-NR52
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // unused
+];
+
+// A machine spinning on jr -2 with the apu powered on
+fn machine() -> RustyBoy {
+    let mut rusty = RustyBoy::new();
+    rusty.load_rom(common::test_rom("apu", &[0x18, 0xfe]));
+    write(&mut rusty, NR52, 0x80);
+    rusty
+}
+
+fn read(rusty: &RustyBoy, address: u16) -> u8 {
+    rusty.read_memory(MemoryView::Bus)[address as usize]
+}
+fn write(rusty: &mut RustyBoy, address: u16, value: u8) {
+    rusty.write_memory(MemoryView::Bus, address as usize, value);
+}
+
+#[test]
+fn registers_read_back_with_unused_bits_set() {
+    let mut rusty = machine();
+    for (n, mask) in READ_MASKS.iter().enumerate() {
+        let address = 0xff10 + n as u16;
+        if address == NR52 {
+            continue;
+        }
+        for value in [0x00, 0xff] {
+            write(&mut rusty, address, value);
+            assert_eq!(read(&rusty, address), value | mask, "${address:04X} after writing ${value:02X}");
+        }
+    }
+}
+
+#[test]
+fn power_off_clears_registers_but_keeps_wave_ram_and_lengths() {
+    let mut rusty = machine();
+    for n in 0..16 {
+        write(&mut rusty, 0xff30 + n, n as u8 * 0x11);
+    }
+    for address in 0xff10..0xff26 {
+        write(&mut rusty, address, 0xff);
+    }
+
+    write(&mut rusty, NR52, 0x00);
+    assert_eq!(read(&rusty, NR52), 0x70);
+    for address in 0xff10..0xff26 {
+        assert_eq!(read(&rusty, address), READ_MASKS[address as usize - 0xff10], "${address:04X}");
+    }
+    // writes are ignored while off, except the length counters
+    write(&mut rusty, NR12, 0xf0);
+    assert_eq!(read(&rusty, NR12), 0x00);
+    write(&mut rusty, NR11, 0x3f);
+
+    write(&mut rusty, NR52, 0x80);
+    for n in 0..16 {
+        assert_eq!(read(&rusty, 0xff30 + n), n as u8 * 0x11, "wave ram ${:04X}", 0xff30 + n);
+    }
+    // the length of 1 written while off still stops the channel
+    write(&mut rusty, NR12, 0xf0);
+    write(&mut rusty, NR14, 0xc0);
+    assert_eq!(read(&rusty, NR52) & 1, 1);
+    rusty.update_and_render();
+    assert_eq!(read(&rusty, NR52) & 1, 0);
+}
+
+#[test]
+fn length_counter_stops_the_channel_only_when_enabled() {
+    let mut rusty = machine();
+    write(&mut rusty, NR12, 0xf0);
+    write(&mut rusty, NR11, 0x3f);
+    // trigger without length, a frame clocks the length several times
+    write(&mut rusty, NR14, 0x80);
+    rusty.update_and_render();
+    assert_eq!(read(&rusty, NR52) & 1, 1);
+
+    write(&mut rusty, NR14, 0xc0);
+    rusty.update_and_render();
+    assert_eq!(read(&rusty, NR52) & 1, 0);
+
+    // the dac being off keeps the trigger from enabling the channel
+    write(&mut rusty, NR12, 0x00);
+    write(&mut rusty, NR14, 0x80);
+    assert_eq!(read(&rusty, NR52) & 1, 0);
+}
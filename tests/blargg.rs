@@ -0,0 +1,63 @@
+use rustyboy::rusty_boy::{MemoryView, RustyBoy};
+use std::path::PathBuf;
+
+// long enough for the slowest dmg_sound rom, about 30 emulated seconds
+const MAX_FRAMES: usize = 1800;
+// blargg tests keep 0x80 at $A000 while running, then the result code, 0 for a pass
+const RUNNING: u8 = 0x80;
+const SIGNATURE: [u8; 3] = [0xde, 0xb0, 0x61];
+
+// The roms of a suite in tests/roms, fetched by CI. Without them the suite is skipped locally,
+// and fails when the CI variable is set so a broken fetch can't pass unnoticed
+fn roms(suite: &str) -> Vec<PathBuf> {
+    let folder = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/roms").join(suite);
+    let Ok(entries) = std::fs::read_dir(&folder) else {
+        assert!(std::env::var_os("CI").is_none(), "no blargg {suite} roms in {}", folder.display());
+        println!("skipping {suite}, copy the blargg roms to {}", folder.display());
+        return Vec::new();
+    };
+    let mut roms: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "gb"))
+        .collect();
+    roms.sort();
+    assert!(!roms.is_empty(), "no roms in {}", folder.display());
+    roms
+}
+
+// Runs a rom until it reports a result in cartridge ram, returns the code and the text it printed
+fn run(rom: PathBuf) -> Option<(u8, String)> {
+    let mut rusty = RustyBoy::new();
+    rusty.load_rom(rom);
+    for frame in 0..MAX_FRAMES {
+        rusty.update_and_render();
+        if frame % 10 != 0 {
+            continue;
+        }
+        let ram = rusty.read_memory(MemoryView::Ram(0));
+        if ram.len() > 4 && ram[1..4] == SIGNATURE && ram[0] != RUNNING {
+            let text = ram[4..].iter().take_while(|b| **b != 0).map(|b| *b as char).collect();
+            return Some((ram[0], text));
+        }
+    }
+    None
+}
+
+#[test]
+fn dmg_sound() {
+    let roms = roms("dmg_sound");
+    if roms.is_empty() {
+        return;
+    }
+    let mut failed = Vec::new();
+    for rom in &roms {
+        let name = rom.file_stem().unwrap().to_string_lossy().into_owned();
+        match run(rom.clone()) {
+            Some((0, _)) => {}
+            Some((code, text)) => failed.push(format!("{name}: code {code}\n{}", text.trim())),
+            None => failed.push(format!("{name}: no result after {MAX_FRAMES} frames")),
+        }
+    }
+    println!("dmg sound {}/{}", roms.len() - failed.len(), roms.len());
+    assert!(failed.is_empty(), "failing roms:\n{}", failed.join("\n"));
+}
@@ -0,0 +1,36 @@
+// Helpers shared by the headless tests
+use rustyboy::rusty_boy::png;
+use std::path::{Path, PathBuf};
+
+pub fn fixture(path: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(path)
+}
+
+// Compares an image with its reference png in the fixtures folder. Running the tests with
+// UPDATE_FIXTURES=1 writes the references instead, check the new images before committing them
+#[allow(dead_code)]
+pub fn assert_matches_reference(name: &str, width: usize, height: usize, rgba: &[u8]) {
+    let path = fixture(name);
+    if std::env::var_os("UPDATE_FIXTURES").is_some() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, png::encode_png(width, height, rgba)).unwrap();
+        return;
+    }
+    match png::compare_with_reference(width, height, rgba, &path) {
+        Ok(0) => {}
+        Ok(count) => panic!("{count} pixels differ from {}", path.display()),
+        Err(error) => panic!("{error}"),
+    }
+}
+
+// Writes a 32k rom with code at the entry point into the temp folder, returns its path
+#[allow(dead_code)]
+pub fn test_rom(name: &str, code: &[u8]) -> PathBuf {
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x100..0x100 + code.len()].copy_from_slice(code);
+    // header checksum over 0x134..0x14d
+    rom[0x14d] = rom[0x134..0x14d].iter().fold(0u8, |sum, b| sum.wrapping_sub(*b).wrapping_sub(1));
+    let path = std::env::temp_dir().join(format!("rustyboy_{}_{}.gb", name, std::process::id()));
+    std::fs::write(&path, rom).unwrap();
+    path
+}
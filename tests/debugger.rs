@@ -0,0 +1,36 @@
+mod common;
+
+use rustyboy::rusty_boy::RustyBoy;
+
+// frames to run before a pause counts as missed
+const MAX_FRAMES: usize = 10;
+
+fn run_until_paused(rusty: &mut RustyBoy) -> u16 {
+    for _ in 0..MAX_FRAMES {
+        rusty.update_and_render();
+        if rusty.debugger().paused() {
+            return rusty.cpu().unwrap().registers.pc;
+        }
+    }
+    panic!("no pause after {MAX_FRAMES} frames");
+}
+
+#[test]
+fn step_out_waits_for_the_ret_of_the_frame() {
+    let mut code = vec![0u8; 0x21];
+    // call $0110, then jr -2 forever
+    code[0x00..0x05].copy_from_slice(&[0xcd, 0x10, 0x01, 0x18, 0xfe]);
+    // push bc, call $0120, pop bc, ret
+    code[0x10..0x16].copy_from_slice(&[0xc5, 0xcd, 0x20, 0x01, 0xc1, 0xc9]);
+    // ret
+    code[0x20] = 0xc9;
+
+    let mut rusty = RustyBoy::new();
+    rusty.load_rom(common::test_rom("step_out", &code));
+    rusty.debugger_mut().toggle_breakpoint(0x111, None);
+    assert_eq!(run_until_paused(&mut rusty), 0x111);
+
+    // neither the nested call nor the pop bc raising sp above the start finish the step out
+    rusty.step_out();
+    assert_eq!(run_until_paused(&mut rusty), 0x103);
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use rustyboy::rusty_boy::filter::Filter;
+
+// DMG greens, lightest first
+const PALETTE: [[u8; 4]; 4] = [[224, 248, 208, 255], [136, 192, 112, 255], [52, 104, 86, 255], [8, 24, 32, 255]];
+
+// 16x12 test card: a shallow and a steep line, a filled diamond, a lone pixel, an L shape and a checker
+const CARD: [&str; 12] = [
+    "0000000000000000",
+    "0333000000001000",
+    "0003330000011100",
+    "0000033300111110",
+    "0200000000011100",
+    "0200000000001000",
+    "0020000030000000",
+    "0020000000002220",
+    "0002000000002000",
+    "0002001010002000",
+    "0000010101000000",
+    "0000001010000000",
+];
+
+fn card() -> (usize, usize, Vec<u8>) {
+    let rgba = CARD
+        .iter()
+        .flat_map(|row| row.bytes().flat_map(|shade| PALETTE[(shade - b'0') as usize]))
+        .collect();
+    (CARD[0].len(), CARD.len(), rgba)
+}
+
+fn check(filter: Filter, name: &str) {
+    let (width, height, rgba) = card();
+    let output = filter.apply(width, height, &rgba);
+    let scale = filter.scale();
+    assert_eq!(output.len(), rgba.len() * scale * scale);
+    common::assert_matches_reference(&format!("filters/{name}.png"), width * scale, height * scale, &output);
+}
+
+#[test]
+fn nearest_is_unchanged() {
+    let (width, height, rgba) = card();
+    assert_eq!(Filter::Nearest.apply(width, height, &rgba), rgba);
+}
+
+#[test]
+fn scale2x_matches_reference() {
+    check(Filter::Scale2x, "scale2x");
+}
+
+#[test]
+fn scale3x_matches_reference() {
+    check(Filter::Scale3x, "scale3x");
+}
+
+#[test]
+fn hq2x_matches_reference() {
+    check(Filter::Hq2x, "hq2x");
+}
+
+#[test]
+fn xbrz2x_matches_reference() {
+    check(Filter::Xbrz2x, "xbrz2x");
+}
+
+#[test]
+fn lcd_grid_matches_reference() {
+    check(Filter::LcdGrid, "lcd_grid");
+}
+
+#[test]
+fn flat_image_stays_flat() {
+    // no edges, so every filter but the lcd grid returns the color unchanged
+    let rgba = PALETTE[1].repeat(6 * 5);
+    for filter in [Filter::Scale2x, Filter::Scale3x, Filter::Hq2x, Filter::Xbrz2x] {
+        let output = filter.apply(6, 5, &rgba);
+        assert!(output.chunks(4).all(|pixel| pixel == PALETTE[1]), "{}", filter.name());
+    }
+}
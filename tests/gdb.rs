@@ -0,0 +1,99 @@
+mod common;
+
+use rustyboy::rusty_boy::RustyBoy;
+use std::io::{ErrorKind, Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+// frames to run while waiting for a reply before giving up
+const MAX_FRAMES: usize = 60;
+
+fn packet(data: &str) -> String {
+    let checksum = data.bytes().fold(0u8, |sum, b| sum.wrapping_add(b));
+    format!("${}#{:02x}", data, checksum)
+}
+
+struct Session {
+    rusty: RustyBoy,
+    stream: TcpStream,
+}
+
+impl Session {
+    fn connect() -> Self {
+        let mut rusty = RustyBoy::new();
+        // nop, jr -3 at the entry point
+        rusty.load_rom(common::test_rom("gdb", &[0x00, 0x18, 0xfd]));
+        let port = rusty.start_gdb(0).unwrap();
+        let stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        stream.set_read_timeout(Some(Duration::from_millis(10))).unwrap();
+        Self { rusty, stream }
+    }
+
+    // Sends raw bytes and runs frames until the server answered with expected_len bytes
+    fn exchange(&mut self, bytes: &str, expected_len: usize) -> String {
+        self.stream.write_all(bytes.as_bytes()).unwrap();
+        let mut received = Vec::new();
+        let mut chunk = [0u8; 4096];
+        for _ in 0..MAX_FRAMES {
+            self.rusty.update_and_render();
+            loop {
+                match self.stream.read(&mut chunk) {
+                    Ok(0) => panic!("server closed the connection"),
+                    Ok(n) => received.extend_from_slice(&chunk[..n]),
+                    Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => break,
+                    Err(e) => panic!("{e}"),
+                }
+            }
+            if received.len() >= expected_len {
+                break;
+            }
+        }
+        String::from_utf8(received).unwrap()
+    }
+
+    // Sends a packet and returns the acknowledged reply
+    fn command(&mut self, data: &str, reply: &str) -> String {
+        let expected = format!("+{}", packet(reply));
+        let received = self.exchange(&packet(data), expected.len());
+        assert_eq!(received, expected, "reply to {data}");
+        received
+    }
+}
+
+#[test]
+fn answers_gdb_packets() {
+    let mut session = Session::connect();
+
+    // attaching stops the target
+    session.command("?", "S05");
+    assert!(session.rusty.debugger().paused());
+
+    // af bc de hl sp pc, pc still at the entry point
+    let registers = session.exchange(&packet("g"), 2 + 24 + 3);
+    assert_eq!(&registers[2..26][20..], "0001", "registers {registers}");
+
+    session.command("m100,3", "0018fd");
+    // long reads are cut to the packet size, reads past the end of the bus stop at $FFFF
+    let long = session.exchange(&packet("m0,ffffff"), 1 + 1 + 0x1000 + 3);
+    assert_eq!(long.len(), 1 + 1 + 0x1000 + 3);
+    let end = session.exchange(&packet("mfffe,10"), 1 + 1 + 4 + 3);
+    assert_eq!(end.len(), 1 + 1 + 4 + 3, "reply {end}");
+
+    session.command("Z0,101,1", "OK");
+    assert!(session.rusty.debugger().has_breakpoint(0x101, None));
+
+    // continue runs into the breakpoint on the jr
+    let stop = session.exchange(&packet("c"), 1 + packet("S05").len());
+    assert_eq!(stop, format!("+{}", packet("S05")));
+    let registers = session.exchange(&packet("g"), 2 + 24 + 3);
+    assert_eq!(&registers[2..26][20..], "0101", "registers {registers}");
+}
+
+#[test]
+fn rejects_bad_checksums() {
+    let mut session = Session::connect();
+    assert_eq!(session.exchange("$?#00", 1), "-");
+    // the next good packet is still answered, acks and garbage in front of it are skipped
+    let expected = format!("+{}", packet("S05"));
+    assert_eq!(session.exchange(&format!("+-junk{}", packet("?")), expected.len()), expected);
+}
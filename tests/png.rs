@@ -0,0 +1,52 @@
+mod common;
+
+use rustyboy::rusty_boy::png;
+
+// 5x3 image with every channel varying, so each png filter type has something to predict
+fn pattern() -> (usize, usize, Vec<u8>) {
+    let (width, height) = (5, 3);
+    let rgba = (0..width * height)
+        .flat_map(|i| [(i * 40) as u8, (i * 17 + 3) as u8, (255 - i * 9) as u8, if i % 4 == 0 { 128 } else { 255 }])
+        .collect();
+    (width, height, rgba)
+}
+
+#[test]
+fn decodes_what_it_encodes() {
+    let (width, height, rgba) = pattern();
+    let decoded = png::decode_png(&png::encode_png(width, height, &rgba)).unwrap();
+    assert_eq!(decoded, (width, height, rgba));
+}
+
+#[test]
+fn decodes_multiple_stored_blocks() {
+    // more than one 64k stored block of image data
+    let (width, height) = (200, 100);
+    let rgba: Vec<u8> = (0..width * height * 4).map(|i| (i % 251) as u8).collect();
+    let decoded = png::decode_png(&png::encode_png(width, height, &rgba)).unwrap();
+    assert_eq!(decoded, (width, height, rgba));
+}
+
+#[test]
+fn rejects_other_files() {
+    assert!(png::decode_png(b"GIF89a").is_err());
+}
+
+#[test]
+fn scaled_screenshot_matches_reference() {
+    let (width, height, rgba) = pattern();
+    let scaled = png::scale_rgba(width, height, &rgba, 3);
+    common::assert_matches_reference("png/pattern_3x.png", width * 3, height * 3, &scaled);
+}
+
+#[test]
+fn reports_differing_pixels() {
+    let (width, height, rgba) = pattern();
+    let scaled = png::scale_rgba(width, height, &rgba, 3);
+    let mut changed = scaled.clone();
+    changed[0] ^= 0xff;
+    changed[4 * 7 + 2] ^= 0x01;
+    let reference = common::fixture("png/pattern_3x.png");
+    assert_eq!(png::compare_with_reference(width * 3, height * 3, &changed, &reference), Ok(2));
+    assert!(png::compare_with_reference(width, height, &rgba, &reference).is_err());
+}
@@ -0,0 +1,41 @@
+mod common;
+
+use rustyboy::rusty_boy::recorder::RecordFormat;
+use rustyboy::rusty_boy::{RustyBoy, RECORD_SAMPLE_RATE};
+
+const FRAMES: usize = 60;
+const CYCLES_PER_FRAME: f64 = 70224.0;
+const CPU_CLOCK: f64 = 4194304.0;
+
+// Sums the sizes of the chunks with an id in the idx1 index at the end of an avi
+fn indexed_bytes(avi: &[u8], id: &[u8; 4]) -> usize {
+    let start = avi.windows(4).rposition(|w| w == b"idx1").unwrap() + 8;
+    avi[start..]
+        .chunks(16)
+        .filter(|entry| &entry[0..4] == id)
+        .map(|entry| u32::from_le_bytes(entry[12..16].try_into().unwrap()) as usize)
+        .sum()
+}
+
+#[test]
+fn avi_audio_follows_emulated_time() {
+    let mut rusty = RustyBoy::new();
+    // jr -2, spins at the entry point
+    rusty.load_rom(common::test_rom("avi", &[0x18, 0xfe]));
+    let folder = std::env::temp_dir().join(format!("rustyboy_avi_{}", std::process::id()));
+    let path = rusty.start_recording(&folder, RecordFormat::Avi).unwrap();
+    for _ in 0..FRAMES {
+        rusty.update_and_render();
+    }
+    rusty.stop_recording();
+
+    let avi = std::fs::read(&path).unwrap();
+    std::fs::remove_dir_all(&folder).unwrap();
+    assert_eq!(indexed_bytes(&avi, b"00db"), FRAMES * 160 * 144 * 3);
+    // 16 bit stereo at the fixed recording rate, whatever the output device runs at
+    let samples = indexed_bytes(&avi, b"01wb") / 4;
+    let expected = FRAMES as f64 * CYCLES_PER_FRAME / CPU_CLOCK * RECORD_SAMPLE_RATE as f64;
+    // the first frame after power on is short and the band limited buffer holds a few samples back,
+    // while a 0.5% rate error would be 240 samples off
+    assert!((samples as f64 - expected).abs() < 100.0, "{samples} samples, expected {expected}");
+}